@@ -0,0 +1,327 @@
+use std::{ffi::OsString, path::PathBuf};
+
+use clap::{Args, CommandFactory, Parser, Subcommand};
+
+use crate::{
+    links::LinkStyle,
+    output::Format,
+    plan::{ConflictStrategy, NestedMode},
+};
+
+/// Move existing Git repositories into the ghq directory layout.
+#[derive(Debug, Parser)]
+#[command(
+    version,
+    about,
+    override_usage = "ghq-mover [OPTIONS] <COMMAND>\n       ghq-mover [OPTIONS] <DIR> [MOVE OPTIONS]",
+    after_help = "Exit status: 0 on success, 1 on error, 2 on invalid usage, 3 when cancelled."
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    #[command(flatten)]
+    pub global: GlobalArgs,
+}
+
+impl Cli {
+    /// 引数を解析する (`ghq-mover <DIR>` は `ghq-mover move <DIR>` として扱う)
+    pub fn parse_args<I, T>(args: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString>,
+    {
+        Cli::try_parse_args(args).unwrap_or_else(|e| e.exit())
+    }
+
+    pub fn try_parse_args<I, T>(args: I) -> Result<Self, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString>,
+    {
+        Cli::try_parse_from(with_default_command(
+            args.into_iter().map(Into::into).collect(),
+        ))
+    }
+}
+
+/// 最初の位置引数がサブコマンド名でなければ、その前に `move` を補う
+///
+/// clap に任せると DIR とサブコマンド名の区別がつかないので、解析する前に決める。
+fn with_default_command(mut args: Vec<OsString>) -> Vec<OsString> {
+    let cli = Cli::command();
+    let takes_value = |flag: &str| {
+        cli.get_arguments().any(|a| {
+            a.get_action().takes_values()
+                && (a.get_long().is_some_and(|l| flag == format!("--{}", l))
+                    || a.get_short().is_some_and(|s| flag == format!("-{}", s)))
+        })
+    };
+
+    let mut i = 1;
+    while let Some(arg) = args.get(i) {
+        let arg = arg.to_string_lossy();
+        if arg == "--" {
+            if i + 1 < args.len() {
+                args.insert(i, "move".into());
+            }
+            break;
+        }
+        if arg.len() > 1 && arg.starts_with('-') {
+            i += if takes_value(&arg) { 2 } else { 1 };
+            continue;
+        }
+        if arg != "help" && cli.find_subcommand(arg.as_ref()).is_none() {
+            args.insert(i, "move".into());
+        }
+        break;
+    }
+    args
+}
+
+#[derive(Debug, Args)]
+pub struct GlobalArgs {
+    /// ghq root to move repositories into (defaults to the primary ghq root;
+    /// `ghq.<url>.root` settings still take precedence for matching remotes)
+    #[arg(long, global = true, value_name = "DIR")]
+    pub root: Option<PathBuf>,
+
+    /// Answer yes to every confirmation prompt
+    #[arg(short, long, global = true)]
+    pub yes: bool,
+
+    /// Answer no to every confirmation prompt
+    #[arg(short, long, global = true, conflicts_with = "yes")]
+    pub no: bool,
+
+    /// Print more details (can be repeated)
+    #[arg(short, long, global = true, action = clap::ArgAction::Count, conflicts_with = "quiet")]
+    pub verbose: u8,
+
+    /// Only print warnings and errors
+    #[arg(short, long, global = true)]
+    pub quiet: bool,
+
+    /// Output format of `scan`, `plan` and `move` results; with `json` and `ndjson`
+    /// everything meant for humans is written to stderr instead
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        value_name = "FORMAT",
+        default_value_t
+    )]
+    pub format: Format,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// List repositories and where they would be moved
+    Scan(ScanArgs),
+    /// Check every move without touching the filesystem
+    Plan(PlanArgs),
+    /// Move repositories into the ghq root
+    Move(MoveArgs),
+    /// Carry out the moves in a plan file written by `plan --output`
+    Apply(ApplyArgs),
+    /// Move repositories back to where they were before a `move`
+    Undo(UndoArgs),
+    /// List the symlinks left by `move --symlink`, or remove them
+    Links(LinksArgs),
+    /// Check the environment ghq-mover runs in
+    Doctor,
+}
+
+#[derive(Debug, Args)]
+pub struct ScanArgs {
+    /// Directory to search for Git repositories
+    pub dir: PathBuf,
+
+    /// Remotes to derive the destination from, in order of preference
+    /// (a repository with a single remote always uses it)
+    #[arg(
+        long = "remote",
+        value_name = "NAME",
+        value_delimiter = ',',
+        default_values = ["origin", "upstream"]
+    )]
+    pub remotes: Vec<String>,
+
+    /// Use SSH host aliases verbatim instead of resolving them via ~/.ssh/config
+    #[arg(long)]
+    pub no_ssh_config: bool,
+
+    /// How to handle repositories nested inside another repository's working tree
+    /// (asks when moving interactively, otherwise defaults to `outer`)
+    #[arg(long, value_enum, value_name = "MODE")]
+    pub nested: Option<NestedMode>,
+
+    /// How to handle several repositories that map to the same destination
+    /// (asks when moving interactively, otherwise defaults to `skip`)
+    #[arg(long, value_enum, value_name = "STRATEGY")]
+    pub on_conflict: Option<ConflictStrategy>,
+
+    /// Leave repositories with uncommitted changes, untracked files, stashes
+    /// or unpushed commits where they are
+    #[arg(long)]
+    pub only_clean: bool,
+
+    /// Do not descend into directories matching GLOB (a pattern without `/` matches
+    /// the directory name at any depth; `.ghqmoverignore` in DIR is read as well)
+    #[arg(long, value_name = "GLOB", value_delimiter = ',')]
+    pub exclude: Vec<String>,
+
+    /// Scan directories matching GLOB even if they are excluded
+    #[arg(long, value_name = "GLOB", value_delimiter = ',')]
+    pub include: Vec<String>,
+
+    /// Only look for repositories up to N directories below DIR
+    #[arg(long, value_name = "N")]
+    pub max_depth: Option<usize>,
+
+    /// Do not descend into directories on other filesystems (mount points)
+    #[arg(long)]
+    pub one_file_system: bool,
+
+    /// Follow symlinks to directories (loops are detected and not followed)
+    #[arg(short = 'L', long)]
+    pub follow_symlinks: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct PlanArgs {
+    #[command(flatten)]
+    pub scan: ScanArgs,
+
+    /// Also write the moves that can be made to FILE, to be edited and run with `apply`
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+}
+
+/// 移動のしかた (`move` と `apply` で共通)
+#[derive(Debug, Args)]
+pub struct ExecArgs {
+    /// Also move linked worktrees that live outside the repository to `<dest>.worktrees/<name>`
+    #[arg(long)]
+    pub relocate_worktrees: bool,
+
+    /// Leave a symlink at each old location pointing to the new one
+    #[arg(long, value_enum, value_name = "STYLE")]
+    pub symlink: Option<LinkStyle>,
+}
+
+#[derive(Debug, Args)]
+pub struct MoveArgs {
+    #[command(flatten)]
+    pub scan: ScanArgs,
+
+    /// Only print the move plan (same as `plan`)
+    #[arg(long)]
+    pub dry_run: bool,
+
+    #[command(flatten)]
+    pub exec: ExecArgs,
+
+    /// Decide for each repository whether to move it, skip it or change its destination
+    #[arg(short, long, conflicts_with = "select")]
+    pub interactive: bool,
+
+    /// Pick the repositories to move from a checklist
+    #[arg(long)]
+    pub select: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ApplyArgs {
+    /// Plan file written by `plan --output`
+    pub file: PathBuf,
+
+    #[command(flatten)]
+    pub exec: ExecArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct UndoArgs {
+    /// Journal of the migration to reverse (defaults to the most recent one)
+    pub journal: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct LinksArgs {
+    /// Remove the symlinks that still point to where the repository was moved
+    #[arg(long)]
+    pub clean: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use clap::CommandFactory;
+
+    use super::{Cli, Command};
+    use crate::output::Format;
+
+    fn command_and_dir(cli: &Cli) -> (&'static str, Option<PathBuf>) {
+        match &cli.command {
+            Some(Command::Scan(args)) => ("scan", Some(args.dir.clone())),
+            Some(Command::Plan(args)) => ("plan", Some(args.scan.dir.clone())),
+            Some(Command::Move(args)) => ("move", Some(args.scan.dir.clone())),
+            Some(Command::Apply(_)) => ("apply", None),
+            Some(Command::Undo(_)) => ("undo", None),
+            Some(Command::Links(_)) => ("links", None),
+            Some(Command::Doctor) => ("doctor", None),
+            None => ("", None),
+        }
+    }
+
+    #[test]
+    fn definition_is_valid() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn parses_global_flags_before_the_command() {
+        let cases: [(&[&str], &str, Option<&str>); 10] = [
+            (&["move", "DIR"], "move", Some("DIR")),
+            (&["--yes", "move", "DIR"], "move", Some("DIR")),
+            (&["-v", "scan", "DIR"], "scan", Some("DIR")),
+            (&["--format", "json", "scan", "DIR"], "scan", Some("DIR")),
+            (&["--root", "ROOT", "plan", "DIR"], "plan", Some("DIR")),
+            (&["scan", "DIR", "--yes"], "scan", Some("DIR")),
+            (&["-q", "doctor"], "doctor", None),
+            (&["DIR"], "move", Some("DIR")),
+            (&["--yes", "DIR", "--only-clean"], "move", Some("DIR")),
+            (
+                &["--root", "scan", "DIR", "--nested", "both"],
+                "move",
+                Some("DIR"),
+            ),
+        ];
+
+        for (args, command, dir) in cases {
+            let argv = std::iter::once("ghq-mover").chain(args.iter().copied());
+            let cli = Cli::try_parse_args(argv).unwrap_or_else(|e| panic!("{:?}: {}", args, e));
+            assert_eq!(
+                command_and_dir(&cli),
+                (command, dir.map(PathBuf::from)),
+                "{:?}",
+                args
+            );
+        }
+    }
+
+    #[test]
+    fn keeps_global_flag_values() {
+        let cli = Cli::parse_args(["ghq-mover", "--yes", "--format", "ndjson", "move", "DIR"]);
+        assert!(cli.global.yes);
+        assert_eq!(cli.global.format, Format::Ndjson);
+
+        let cli = Cli::parse_args(["ghq-mover", "-v", "-v", "DIR", "--only-clean"]);
+        assert_eq!(cli.global.verbose, 2);
+        match cli.command {
+            Some(Command::Move(args)) => assert!(args.scan.only_clean),
+            other => panic!("{:?}", other),
+        }
+    }
+}
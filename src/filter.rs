@@ -0,0 +1,172 @@
+use std::{fs, path::Path};
+
+use anyhow::{Context, Result};
+use globset::{GlobBuilder, GlobMatcher};
+
+/// 走査するディレクトリのルートに置く除外設定
+pub const IGNORE_FILE: &str = ".ghqmoverignore";
+
+/// 走査しないディレクトリを glob で指定する
+///
+/// `.gitignore` と同様に、`/` を含まないパターンはどの階層のディレクトリ名にも一致し、
+/// `/` を含むパターンは走査するディレクトリからの相対パスに一致する。
+/// 複数のパターンに一致した場合は後のものが優先される。
+/// 除外したディレクトリの中は走査しないので、その中のディレクトリを再び含めることはできない。
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    matcher: GlobMatcher,
+    /// 相対パス全体と比較する
+    anchored: bool,
+    /// `false` なら除外の取り消し (`--include` や `!pattern`)
+    exclude: bool,
+}
+
+impl Filter {
+    /// `base` の `.ghqmoverignore` と、コマンドラインの `--exclude` / `--include` から作る
+    pub fn new(base: &Path, exclude: &[String], include: &[String]) -> Result<Self> {
+        let mut filter = Filter::default();
+
+        let ignore_file = base.join(IGNORE_FILE);
+        if let Ok(content) = fs::read_to_string(&ignore_file) {
+            for (i, line) in content.lines().enumerate() {
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                let (pattern, exclude) = match line.strip_prefix('!') {
+                    Some(pattern) => (pattern, false),
+                    None => (line, true),
+                };
+                filter
+                    .push(pattern, exclude)
+                    .with_context(|| format!("{}:{}", ignore_file.display(), i + 1))?;
+            }
+        }
+        for pattern in exclude {
+            filter.push(pattern, true)?;
+        }
+        for pattern in include {
+            filter.push(pattern, false)?;
+        }
+        Ok(filter)
+    }
+
+    fn push(&mut self, pattern: &str, exclude: bool) -> Result<()> {
+        let pattern = pattern.trim_end_matches('/');
+        let anchored = pattern.contains('/');
+        let glob = GlobBuilder::new(pattern.trim_start_matches('/'))
+            .literal_separator(true)
+            .build()
+            .with_context(|| format!("Invalid glob {:?}", pattern))?;
+        self.rules.push(Rule {
+            matcher: glob.compile_matcher(),
+            anchored,
+            exclude,
+        });
+        Ok(())
+    }
+
+    /// `relative` (走査するディレクトリからの相対パス) のディレクトリを走査しないか
+    pub fn is_excluded(&self, relative: &Path) -> bool {
+        let Some(name) = relative.file_name() else {
+            return false;
+        };
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| {
+                if rule.anchored {
+                    rule.matcher.is_match(relative)
+                } else {
+                    rule.matcher.is_match(name)
+                }
+            })
+            .is_some_and(|rule| rule.exclude)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use super::{Filter, IGNORE_FILE};
+
+    fn strings(patterns: &[&str]) -> Vec<String> {
+        patterns.iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn excludes_matching_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join(IGNORE_FILE),
+            "# comment\n\nnode_modules\na/b\n/build/\nvendor*\n!vendor-keep\n",
+        )
+        .unwrap();
+        let filter = Filter::new(dir.path(), &[], &[]).unwrap();
+
+        let cases = [
+            // `/` を含まないパターンはどの階層のディレクトリ名にも一致する
+            ("node_modules", true),
+            ("x/node_modules", true),
+            ("x/y/node_modules", true),
+            ("node_modules_old", false),
+            // `/` を含むパターンは相対パス全体に一致する
+            ("a/b", true),
+            ("x/a/b", false),
+            ("a/b/c", false),
+            ("build", true),
+            ("x/build", false),
+            ("vendor", true),
+            ("vendor-old", true),
+            // `!` で除外を取り消す
+            ("vendor-keep", false),
+            ("src", false),
+        ];
+        for (path, excluded) in cases {
+            assert_eq!(filter.is_excluded(Path::new(path)), excluded, "{}", path);
+        }
+    }
+
+    #[test]
+    fn later_rules_win() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join(IGNORE_FILE), "keep\n").unwrap();
+        let filter = Filter::new(
+            dir.path(),
+            &strings(&["node_modules", "tmp*"]),
+            &strings(&["tmp-keep", "keep/node_modules"]),
+        )
+        .unwrap();
+
+        let cases = [
+            ("keep", true),
+            ("tmp", true),
+            // --include は --exclude より後に評価される
+            ("tmp-keep", false),
+            ("x/node_modules", true),
+            ("keep/node_modules", false),
+        ];
+        for (path, excluded) in cases {
+            assert_eq!(filter.is_excluded(Path::new(path)), excluded, "{}", path);
+        }
+
+        let filter = Filter::new(dir.path(), &[], &strings(&["keep"])).unwrap();
+        assert!(!filter.is_excluded(Path::new("keep")));
+    }
+
+    #[test]
+    fn reports_invalid_patterns() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(Filter::new(dir.path(), &strings(&["a[b"]), &[]).is_err());
+
+        fs::write(dir.path().join(IGNORE_FILE), "ok\n[\n").unwrap();
+        let err = Filter::new(dir.path(), &[], &[]).unwrap_err();
+        assert!(format!("{:#}", err).contains(":2"), "{:#}", err);
+    }
+}
@@ -0,0 +1,168 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use git_url_parse::GitUrl;
+use git2::Config;
+
+use crate::layout;
+
+/// ghq のルートディレクトリをどこから決めたか
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RootSource {
+    Env,
+    GitConfig,
+    Default,
+}
+
+/// ghq と同じ規則で解決したルートディレクトリの一覧 (先頭がプライマリ)
+#[derive(Debug, Clone)]
+pub struct Roots {
+    pub roots: Vec<PathBuf>,
+    pub source: RootSource,
+}
+
+impl Roots {
+    /// `$GHQ_ROOT` (パス区切りで複数可)、git config の `ghq.root` (複数可)、`~/ghq` の順に探す
+    pub fn load() -> Result<Self> {
+        if let Some(value) = env::var_os("GHQ_ROOT").filter(|v| !v.is_empty()) {
+            let roots = env::split_paths(&value)
+                .filter(|p| !p.as_os_str().is_empty())
+                .map(|p| normalize(&p))
+                .collect::<Result<Vec<_>>>()?;
+            if !roots.is_empty() {
+                return Ok(Roots {
+                    roots,
+                    source: RootSource::Env,
+                });
+            }
+        }
+
+        let config = Config::open_default().context("Failed to read git config")?;
+        let mut values = Vec::new();
+        if let Ok(entries) = config.multivar("ghq.root", None) {
+            entries.for_each(|entry| {
+                if let Some(value) = entry.value() {
+                    values.push(value.to_string());
+                }
+            })?;
+        }
+        if !values.is_empty() {
+            let roots = values
+                .iter()
+                .map(|v| normalize(Path::new(v)))
+                .collect::<Result<Vec<_>>>()?;
+            return Ok(Roots {
+                roots,
+                source: RootSource::GitConfig,
+            });
+        }
+
+        let home = env::home_dir().context("Failed to find home directory")?;
+        Ok(Roots {
+            roots: vec![normalize(&home.join("ghq"))?],
+            source: RootSource::Default,
+        })
+    }
+
+    pub fn primary(&self) -> &Path {
+        &self.roots[0]
+    }
+}
+
+/// `~` を展開して絶対パスにし、存在すればシンボリックリンクを解決する
+pub fn normalize(path: &Path) -> Result<PathBuf> {
+    let expanded = match path.strip_prefix("~") {
+        Ok(rest) => env::home_dir()
+            .context("Failed to find home directory")?
+            .join(rest),
+        Err(_) => path.to_path_buf(),
+    };
+    let absolute =
+        std::path::absolute(&expanded).with_context(|| format!("Invalid path {:?}", path))?;
+    Ok(fs::canonicalize(&absolute).unwrap_or(absolute))
+}
+
+/// `[ghq "https://github.com/mycompany/"] root = ~/work` のような URL ごとのルート指定
+#[derive(Debug, Clone)]
+pub struct UrlRoot {
+    pub prefix: String,
+    host: String,
+    segments: Vec<String>,
+    pub root: PathBuf,
+}
+
+impl UrlRoot {
+    /// git config の `ghq.<url>.root` をすべて読み込む
+    pub fn load_all() -> Result<Vec<Self>> {
+        let config = Config::open_default().context("Failed to read git config")?;
+        let mut entries = Vec::new();
+        config.entries(Some(r"ghq\..*\.root"))?.for_each(|entry| {
+            if let (Some(name), Some(value)) = (entry.name(), entry.value()) {
+                entries.push((name.to_string(), value.to_string()));
+            }
+        })?;
+
+        let mut url_roots = Vec::new();
+        for (name, value) in entries {
+            let Some(prefix) = name
+                .strip_prefix("ghq.")
+                .and_then(|n| n.strip_suffix(".root"))
+            else {
+                continue;
+            };
+            let Ok(url) = GitUrl::parse(prefix) else {
+                warn!("⚠️  Ignoring ghq.{}.root: not a URL", prefix);
+                continue;
+            };
+            let (host, path) = layout::host_and_path(&url);
+            if host.is_empty() {
+                continue;
+            }
+            url_roots.push(UrlRoot {
+                prefix: prefix.to_string(),
+                host: host.to_lowercase(),
+                segments: path_segments(path),
+                root: normalize(Path::new(&value))?,
+            });
+        }
+        Ok(url_roots)
+    }
+
+    /// スキームやユーザー名は無視し、ホストとパスの前方一致 (セグメント単位) で判定する
+    fn matches(&self, host: &str, path: &str) -> bool {
+        if !host.eq_ignore_ascii_case(&self.host) {
+            return false;
+        }
+        let segments = path_segments(path);
+        segments.len() >= self.segments.len()
+            && self.segments.iter().zip(&segments).all(|(a, b)| a == b)
+    }
+}
+
+/// リモート URL ごとに移動先のルートを決める
+#[derive(Debug, Clone)]
+pub struct Destinations {
+    pub root: PathBuf,
+    pub url_roots: Vec<UrlRoot>,
+}
+
+impl Destinations {
+    /// 最も長く一致した `ghq.<url>.root` のルート、なければ既定のルート
+    pub fn root_for(&self, host: &str, path: &str) -> &Path {
+        self.url_roots
+            .iter()
+            .filter(|r| r.matches(host, path))
+            .max_by_key(|r| r.segments.len())
+            .map_or(&self.root, |r| &r.root)
+    }
+}
+
+fn path_segments(path: &str) -> Vec<String> {
+    path.split('/')
+        .filter(|s| !s.is_empty())
+        .map(|s| s.trim_end_matches(".git").to_string())
+        .collect()
+}
@@ -0,0 +1,287 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    process,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+use git2::Repository;
+use serde::{Deserialize, Serialize};
+
+/// 1 回の移動の状態 (同じ `id` の行は後のものが有効)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    /// 移動を始めたが終わったかどうか分からない
+    Pending,
+    Done,
+    Failed,
+    /// `undo` で元に戻した
+    Undone,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Action {
+    Repo,
+    /// `--relocate-worktrees` によるワークツリーの移動 (`repo` はその時点のリポジトリの場所)
+    Worktree {
+        repo: PathBuf,
+        name: String,
+    },
+    /// `--symlink` で移動前の場所 (`src`) に残した、移動先 (`dest`) へのリンク
+    Symlink,
+}
+
+/// ジャーナルの 1 行
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub id: usize,
+    /// UNIX 時間 (秒)
+    pub timestamp: u64,
+    pub status: Status,
+    pub src: PathBuf,
+    pub dest: PathBuf,
+    /// 移動前の HEAD (移動後に変更されていないかの確認に使う)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub head: Option<String>,
+    #[serde(flatten)]
+    pub action: Action,
+}
+
+/// 移動の記録 (JSON Lines)
+///
+/// 移動の前に `pending`、後に `done` / `failed` の行を追記するので、
+/// 途中で中断されてもどこまで進んだかが分かる。
+pub struct Journal {
+    path: PathBuf,
+    file: File,
+    next_id: usize,
+}
+
+impl Journal {
+    /// 新しいジャーナルを作る
+    pub fn create() -> Result<Self> {
+        let dir = dir()?;
+        fs::create_dir_all(&dir).with_context(|| format!("Failed to create {:?}", dir))?;
+        let path = dir.join(format!("{}-{}.jsonl", now(), process::id()));
+        Self::open(&path)
+    }
+
+    /// 既存のジャーナルに追記する
+    pub fn open(path: &Path) -> Result<Self> {
+        let next_id = if path.exists() {
+            read(path)?.iter().map(|e| e.id + 1).max().unwrap_or(0)
+        } else {
+            0
+        };
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open journal {:?}", path))?;
+        Ok(Journal {
+            path: path.to_path_buf(),
+            file,
+            next_id,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 移動を始める前に記録する
+    pub fn begin(&mut self, action: Action, src: &Path, dest: &Path) -> Result<Entry> {
+        let entry = Entry {
+            id: self.next_id,
+            timestamp: now(),
+            status: Status::Pending,
+            src: src.to_path_buf(),
+            dest: dest.to_path_buf(),
+            head: head(src),
+            action,
+        };
+        self.next_id += 1;
+        self.write(&entry)?;
+        Ok(entry)
+    }
+
+    /// 移動の結果を記録する
+    pub fn finish(&mut self, entry: &Entry, status: Status) -> Result<()> {
+        self.write(&Entry {
+            timestamp: now(),
+            status,
+            ..entry.clone()
+        })
+    }
+
+    fn write(&mut self, entry: &Entry) -> Result<()> {
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+        self.file
+            .write_all(line.as_bytes())
+            .and_then(|_| self.file.sync_data())
+            .with_context(|| format!("Failed to write journal {:?}", self.path))
+    }
+}
+
+/// ジャーナルを置くディレクトリ
+pub fn dir() -> Result<PathBuf> {
+    let data_dir = dirs::data_dir().context("Could not determine the data directory")?;
+    Ok(data_dir.join("ghq-mover").join("journal"))
+}
+
+/// すべてのジャーナル (古い順)
+pub fn all() -> Result<Vec<PathBuf>> {
+    let Ok(entries) = fs::read_dir(dir()?) else {
+        return Ok(Vec::new());
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|ext| ext == "jsonl"))
+        .collect();
+    paths.sort_by_cached_key(|p| {
+        // `<timestamp>-<pid>.jsonl`
+        let stem = p.file_stem().unwrap_or_default().to_string_lossy();
+        let timestamp = stem.split('-').next().unwrap_or("");
+        (timestamp.parse::<u64>().unwrap_or(0), p.clone())
+    });
+    Ok(paths)
+}
+
+/// 最も新しいジャーナル
+pub fn latest() -> Result<Option<PathBuf>> {
+    Ok(all()?.pop())
+}
+
+/// すべての行を読む
+pub fn read(path: &Path) -> Result<Vec<Entry>> {
+    let file = File::open(path).with_context(|| format!("Failed to open journal {:?}", path))?;
+    let mut entries = Vec::new();
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry = serde_json::from_str(&line)
+            .with_context(|| format!("{}:{}: invalid journal entry", path.display(), i + 1))?;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// 移動ごとの最新の状態を、移動した順に返す
+pub fn latest_entries(entries: Vec<Entry>) -> Vec<Entry> {
+    let mut latest: Vec<Entry> = Vec::new();
+    for entry in entries {
+        match latest.iter_mut().find(|e| e.id == entry.id) {
+            Some(e) => *e = entry,
+            None => latest.push(entry),
+        }
+    }
+    latest
+}
+
+/// リポジトリ (またはワークツリー) の HEAD が指すコミット
+pub fn head(path: &Path) -> Option<String> {
+    let repo = Repository::open(path).ok()?;
+    let oid = repo.head().ok()?.target()?;
+    Some(oid.to_string())
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::{Action, Journal, Status, latest_entries, read};
+
+    fn summary(path: &Path) -> Vec<(usize, Status, PathBuf, Action)> {
+        latest_entries(read(path).unwrap())
+            .into_iter()
+            .map(|e| (e.id, e.status, e.src, e.action))
+            .collect()
+    }
+
+    #[test]
+    fn keeps_the_last_status_of_each_move_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal.jsonl");
+        let mut journal = Journal::open(&path).unwrap();
+
+        let a = journal
+            .begin(Action::Repo, "/src/a".as_ref(), "/ghq/a".as_ref())
+            .unwrap();
+        let b = journal
+            .begin(Action::Repo, "/src/b".as_ref(), "/ghq/b".as_ref())
+            .unwrap();
+        journal.finish(&b, Status::Failed).unwrap();
+        journal.finish(&a, Status::Done).unwrap();
+        let worktree = Action::Worktree {
+            repo: PathBuf::from("/ghq/a"),
+            name: "wt".to_string(),
+        };
+        let wt = journal
+            .begin(
+                worktree.clone(),
+                "/src/wt".as_ref(),
+                "/ghq/a.worktrees/wt".as_ref(),
+            )
+            .unwrap();
+        journal.finish(&wt, Status::Done).unwrap();
+        // 中断された移動は pending のまま残る
+        journal
+            .begin(Action::Symlink, "/src/a".as_ref(), "/ghq/a".as_ref())
+            .unwrap();
+
+        assert_eq!(
+            summary(&path),
+            [
+                (0, Status::Done, PathBuf::from("/src/a"), Action::Repo),
+                (1, Status::Failed, PathBuf::from("/src/b"), Action::Repo),
+                (2, Status::Done, PathBuf::from("/src/wt"), worktree),
+                (3, Status::Pending, PathBuf::from("/src/a"), Action::Symlink),
+            ]
+        );
+    }
+
+    #[test]
+    fn continues_ids_when_reopened() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal.jsonl");
+        let a = {
+            let mut journal = Journal::open(&path).unwrap();
+            let a = journal
+                .begin(Action::Repo, "/src/a".as_ref(), "/ghq/a".as_ref())
+                .unwrap();
+            journal.finish(&a, Status::Done).unwrap();
+            a
+        };
+
+        // `undo` や `links --clean` は既存のジャーナルに追記する
+        let mut journal = Journal::open(&path).unwrap();
+        journal.finish(&a, Status::Undone).unwrap();
+        let link = journal
+            .begin(Action::Symlink, "/src/a".as_ref(), "/ghq/a".as_ref())
+            .unwrap();
+        assert_eq!(link.id, 1);
+        journal.finish(&link, Status::Done).unwrap();
+
+        assert_eq!(
+            summary(&path),
+            [
+                (0, Status::Undone, PathBuf::from("/src/a"), Action::Repo),
+                (1, Status::Done, PathBuf::from("/src/a"), Action::Symlink),
+            ]
+        );
+    }
+}
@@ -0,0 +1,100 @@
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+
+use crate::repair;
+
+/// 移動前の場所に残すシンボリックリンクの書き方
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum LinkStyle {
+    /// Point to the new location relative to the symlink
+    Relative,
+    /// Point to the absolute path of the new location
+    Absolute,
+}
+
+/// 残したシンボリックリンクの現在の状態
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkState {
+    /// 移動先を指している
+    Active,
+    /// 移動先がなくなっている
+    Dangling,
+    /// 別のものに置き換えられている
+    Replaced,
+    Missing,
+}
+
+impl fmt::Display for LinkState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LinkState::Active => write!(f, "active"),
+            LinkState::Dangling => write!(f, "dangling (the target no longer exists)"),
+            LinkState::Replaced => write!(f, "replaced by something else; left alone"),
+            LinkState::Missing => write!(f, "already removed"),
+        }
+    }
+}
+
+/// `link` に `target` を指すシンボリックリンクを作る
+pub fn create(link: &Path, target: &Path, style: LinkStyle) -> Result<()> {
+    let content = match style {
+        LinkStyle::Relative => repair::relative(target, link.parent().unwrap_or(link)),
+        LinkStyle::Absolute => target.to_path_buf(),
+    };
+    symlink_dir(&content, link).with_context(|| format!("Failed to create symlink {:?}", link))
+}
+
+/// `link` が今も `target` を指すシンボリックリンクかどうか
+pub fn state(link: &Path, target: &Path) -> LinkState {
+    let Ok(content) = fs::read_link(link) else {
+        return if fs::symlink_metadata(link).is_ok() {
+            LinkState::Replaced
+        } else {
+            LinkState::Missing
+        };
+    };
+    if resolve(link, &content) != repair::clean(target) {
+        LinkState::Replaced
+    } else if target.exists() {
+        LinkState::Active
+    } else {
+        LinkState::Dangling
+    }
+}
+
+/// `ghq-mover` が作ったリンクのままであれば削除する
+pub fn remove(link: &Path, target: &Path) -> Result<LinkState> {
+    let state = state(link, target);
+    if matches!(state, LinkState::Active | LinkState::Dangling) {
+        remove_symlink_dir(link).with_context(|| format!("Failed to remove symlink {:?}", link))?;
+    }
+    Ok(state)
+}
+
+fn resolve(link: &Path, content: &Path) -> PathBuf {
+    repair::clean(&link.parent().unwrap_or(link).join(content))
+}
+
+#[cfg(unix)]
+fn symlink_dir(content: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(content, link)
+}
+
+#[cfg(windows)]
+fn symlink_dir(content: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::windows::fs::symlink_dir(content, link)
+}
+
+#[cfg(unix)]
+fn remove_symlink_dir(link: &Path) -> std::io::Result<()> {
+    fs::remove_file(link)
+}
+
+#[cfg(windows)]
+fn remove_symlink_dir(link: &Path) -> std::io::Result<()> {
+    fs::remove_dir(link)
+}
@@ -1,128 +1,876 @@
-use std::{
-    env, fs,
-    io::{self, Write},
-    path::{Path, PathBuf},
+use std::{env, fs, path::PathBuf, process::ExitCode};
+
+use anyhow::{Context, Result, bail};
+
+#[macro_use]
+mod output;
+
+mod cli;
+mod filter;
+mod ghq;
+mod journal;
+mod layout;
+mod links;
+mod mover;
+mod plan;
+mod planfile;
+mod prompt;
+mod repair;
+mod report;
+mod rewrite;
+mod safety;
+mod scan;
+mod ssh_config;
+
+use cli::{
+    ApplyArgs, Cli, Command, ExecArgs, GlobalArgs, LinksArgs, MoveArgs, PlanArgs, ScanArgs,
+    UndoArgs,
 };
 
-use anyhow::{Context, Result};
-use git2::Repository;
-use walkdir::WalkDir;
+/// 実行結果 (終了コードに対応)
+enum Outcome {
+    Completed,
+    Cancelled,
+}
 
-fn main() {
-    if let Err(err) = main_inner() {
-        eprintln!("Error: {:?}", err);
-        std::process::exit(1);
+fn main() -> ExitCode {
+    match main_inner() {
+        Ok(Outcome::Completed) => ExitCode::SUCCESS,
+        Ok(Outcome::Cancelled) => ExitCode::from(3),
+        Err(err) if err.is::<prompt::Cancelled>() => {
+            info!("🚫 Operation cancelled.");
+            ExitCode::from(3)
+        }
+        Err(err) => {
+            eprintln!("Error: {:?}", err);
+            ExitCode::FAILURE
+        }
     }
 }
 
-fn main_inner() -> Result<()> {
-    let args: Vec<String> = env::args().collect();
+fn main_inner() -> Result<Outcome> {
+    let cli = Cli::parse_args(env::args_os());
 
-    if args.len() < 2 {
-        eprintln!("Usage: {} <directory>", args[0]);
-        std::process::exit(1);
+    output::set_verbosity(if cli.global.quiet {
+        -1
+    } else {
+        cli.global.verbose as i8
+    });
+    output::set_format(cli.global.format);
+
+    let Some(command) = cli.command else {
+        use clap::CommandFactory;
+        Cli::command().print_help()?;
+        std::process::exit(2);
+    };
+
+    match command {
+        Command::Scan(args) => scan(&cli.global, &args)?,
+        Command::Plan(args) => show_plan(&cli.global, &args)?,
+        Command::Move(args) if args.dry_run => show_plan(
+            &cli.global,
+            &PlanArgs {
+                scan: args.scan,
+                output: None,
+            },
+        )?,
+        Command::Move(args) => return move_repos(&cli.global, &args),
+        Command::Apply(args) => return apply(&cli.global, &args),
+        Command::Undo(args) => return undo(&cli.global, &args),
+        Command::Links(args) => return links(&cli.global, &args),
+        Command::Doctor => doctor(&cli.global)?,
     }
+    Ok(Outcome::Completed)
+}
 
-    let target_dir = PathBuf::from(&args[1]).canonicalize()?;
-    println!("🔍 Searching for Git repositories in {:?}", target_dir);
+fn search(global: &GlobalArgs, args: &ScanArgs, interactive: bool) -> Result<scan::Scan> {
+    let target_dir = args
+        .dir
+        .canonicalize()
+        .with_context(|| format!("Failed to open {:?}", args.dir))?;
+    let dests = destinations(global, interactive)?;
+    info!("🔍 Searching for Git repositories in {:?}", target_dir);
+    debug!("📁 ghq root: {:?}", dests.root);
+    for url_root in &dests.url_roots {
+        debug!("📁 ghq root for {}: {:?}", url_root.prefix, url_root.root);
+    }
 
-    let repos = find_git_repos(&target_dir)?;
+    let opts = scan::Options {
+        dests,
+        remotes: args.remotes.clone(),
+        ssh_config: (!args.no_ssh_config).then(ssh_config::SshConfig::load),
+        filter: filter::Filter::new(&target_dir, &args.exclude, &args.include)?,
+        max_depth: args.max_depth,
+        one_file_system: args.one_file_system,
+        follow_links: args.follow_symlinks,
+    };
+    let mut scan = scan::find_git_repos(&target_dir, &opts)?;
 
-    if repos.is_empty() {
-        println!("⚠️  No Git repositories found.");
+    let nested = plan::mark_nested(&mut scan.repos);
+    if nested > 0 {
+        info!(
+            "🪆 {} repositories are nested inside another repository.",
+            nested
+        );
+        let mode = match args.nested {
+            Some(mode) => mode,
+            None if interactive => {
+                let modes = [
+                    plan::NestedMode::Outer,
+                    plan::NestedMode::Both,
+                    plan::NestedMode::Inner,
+                ];
+                let items = [
+                    "outer: move only the outer repositories (nested ones move with them)",
+                    "both: extract nested repositories first, then move the outer ones",
+                    "inner: extract nested repositories and leave the outer ones in place",
+                ]
+                .map(str::to_string);
+                modes[prompt::select(global, "How should nested repositories be handled?", &items)?]
+            }
+            None => plan::NestedMode::Outer,
+        };
+        plan::resolve_nesting(&mut scan, mode);
+    }
+
+    if args.only_clean {
+        let (clean, risky): (Vec<_>, Vec<_>) =
+            scan.repos.into_iter().partition(|r| r.safety.is_clean());
+        scan.repos = clean;
+        scan.skipped
+            .extend(risky.into_iter().map(|r| scan::Skipped {
+                path: r.src,
+                reason: scan::SkipReason::NotClean(r.safety),
+            }));
+    }
+
+    let conflicts = plan::find_conflicts(&scan.repos);
+    if !conflicts.is_empty() {
+        info!("👯 Several repositories map to the same destination:");
+        for conflict in &conflicts {
+            info!("- {:?}", conflict.dest);
+            for candidate in &conflict.candidates {
+                info!("    {:?} ({})", candidate.src, candidate);
+            }
+        }
+        let strategy = match args.on_conflict {
+            Some(strategy) => strategy,
+            None if interactive => {
+                let strategies = [
+                    plan::ConflictStrategy::Skip,
+                    plan::ConflictStrategy::Newest,
+                    plan::ConflictStrategy::MostRefs,
+                    plan::ConflictStrategy::Suffix,
+                ];
+                let items = [
+                    "skip: leave all of them in place",
+                    "newest: move the one with the most recent commit",
+                    "most-refs: move the one with the most branches and tags",
+                    "suffix: move all of them, numbering the destinations of all but the newest",
+                ]
+                .map(str::to_string);
+                strategies[prompt::select(
+                    global,
+                    "How should repositories with the same destination be handled?",
+                    &items,
+                )?]
+            }
+            None => plan::ConflictStrategy::Skip,
+        };
+        plan::resolve_conflicts(&mut scan, &conflicts, strategy);
+    }
+
+    if scan.repos.is_empty() {
+        info!("⚠️  No Git repositories found.");
+    }
+    Ok(scan)
+}
+
+fn kind_label(repo: &scan::Repo) -> String {
+    match repo.kind {
+        scan::RepoKind::Normal => String::new(),
+        kind => format!(" [{}]", kind),
+    }
+}
+
+fn print_safety(repo: &scan::Repo) {
+    if !repo.safety.is_clean() {
+        say!("  🧺 {}", repo.safety);
+    }
+}
+
+/// 未保存の作業があるリポジトリをまとめて警告する
+fn print_risky(repos: &[scan::Repo]) {
+    let risky: Vec<_> = repos.iter().filter(|r| !r.safety.is_clean()).collect();
+    if risky.is_empty() {
+        return;
+    }
+    warn!(
+        "🧺 {} of {} repositories have work that is not committed or pushed (use --only-clean to leave them in place):",
+        risky.len(),
+        repos.len()
+    );
+    for r in risky {
+        warn!("- {:?}\n  {}\n", r.src, r.safety);
+    }
+}
+
+fn print_worktrees(repo: &scan::Repo) {
+    for worktree in &repo.worktrees {
+        say!("  🌿 worktree {:?} at {:?}", worktree.name, worktree.path);
+    }
+}
+
+fn print_skipped(skipped: &[scan::Skipped]) {
+    if skipped.is_empty() {
+        return;
+    }
+    warn!("⏭️  Skipped repositories ({}):", skipped.len());
+    for s in skipped {
+        warn!("- {:?}\n  {}\n", s.path, s.reason);
+    }
+}
+
+fn scan(global: &GlobalArgs, args: &ScanArgs) -> Result<()> {
+    let scan = search(global, args, false)?;
+    if global.format != output::Format::Text {
+        let mut report = report::Report::new(global.format)?;
+        for r in &scan.repos {
+            report.repository(report::RepoRecord::new(r, "found", None))?;
+        }
+        report.skipped(&scan.skipped)?;
+        return report.finish();
+    }
+
+    if !scan.repos.is_empty() {
+        info!("\n✅ Found repositories:");
+        for r in &scan.repos {
+            say!("- {:?}{}\n  → {:?}", r.src, kind_label(r), r.dest);
+            debug!("  via remote {:?} ({})", r.remote, r.url);
+            print_safety(r);
+            print_worktrees(r);
+            say!("");
+        }
+    }
+    print_skipped(&scan.skipped);
+    Ok(())
+}
+
+/// 実際には移動せず、各リポジトリの移動可否を表示する
+///
+/// `--output` があれば、移動できるものを `apply` で実行できる計画ファイルに書き出す。
+fn show_plan(global: &GlobalArgs, args: &PlanArgs) -> Result<()> {
+    let scan = search(global, &args.scan, false)?;
+    let repos = scan.repos;
+    let verdicts = plan::check_moves(&repos);
+
+    if let Some(path) = &args.output {
+        let movable: Vec<&scan::Repo> = repos
+            .iter()
+            .zip(&verdicts)
+            .filter_map(|(r, verdict)| verdict.is_ok().then_some(r))
+            .collect();
+        planfile::write(path, &movable)?;
+        info!(
+            "📝 Wrote {} of {} moves to {:?}. Edit it, then run `ghq-mover apply {}`.",
+            movable.len(),
+            repos.len(),
+            path,
+            path.display()
+        );
+    }
+
+    if repos.is_empty() && global.format == output::Format::Text {
+        print_skipped(&scan.skipped);
         return Ok(());
     }
 
-    println!("\n✅ Found repositories:");
+    if global.format != output::Format::Text {
+        let mut report = report::Report::new(global.format)?;
+        for (r, verdict) in repos.iter().zip(&verdicts) {
+            let record = report::RepoRecord::new(r, verdict.code(), Some(verdict.to_string()));
+            report.repository(record)?;
+        }
+        report.skipped(&scan.skipped)?;
+        return report.finish();
+    }
+
+    info!("\n📝 Move plan (dry run):");
+    for (r, verdict) in repos.iter().zip(&verdicts) {
+        let mark = if verdict.is_ok() { "✅" } else { "⚠️ " };
+        say!(
+            "{} {:?}{}\n  → {:?}\n  {}",
+            mark,
+            r.src,
+            kind_label(r),
+            r.dest,
+            verdict
+        );
+        if let Some(outer) = &r.nested_in {
+            say!("  🪆 nested in {:?}", outer);
+        }
+        print_safety(r);
+        print_worktrees(r);
+        say!("");
+    }
+
+    print_skipped(&scan.skipped);
+    print_risky(&repos);
+
+    let ok = verdicts.iter().filter(|v| v.is_ok()).count();
+    info!(
+        "📊 {} of {} repositories can be moved. Nothing was changed.",
+        ok,
+        verdicts.len()
+    );
+    Ok(())
+}
+
+fn move_repos(global: &GlobalArgs, args: &MoveArgs) -> Result<Outcome> {
+    let scan = search(global, &args.scan, true)?;
+    let mut repos = scan.repos;
+    // 人向けの表示とは別に、結果を stdout に出力する
+    let mut report = (global.format != output::Format::Text)
+        .then(|| report::Report::new(global.format))
+        .transpose()?;
+    if let Some(report) = &mut report {
+        report.skipped(&scan.skipped)?;
+    }
+    if repos.is_empty() {
+        print_skipped(&scan.skipped);
+        return finish_report(report).map(|_| Outcome::Completed);
+    }
+
+    info!("\n✅ Found repositories:");
     for r in &repos {
-        println!("- {:?}\n  → {:?}\n", r.0, r.1);
+        info!("- {:?}{}\n  → {:?}\n", r.src, kind_label(r), r.dest);
     }
+    print_skipped(&scan.skipped);
+    print_risky(&repos);
 
-    if !confirm("Do you want to move these repositories to ~/ghq? [y/N]: ")? {
-        println!("🚫 Operation cancelled.");
-        return Ok(());
+    // --yes / --no が指定されていれば個別には尋ねない
+    let confirmed = if (args.interactive || args.select) && !global.yes && !global.no {
+        repos = if args.interactive {
+            choose_each(repos)?
+        } else {
+            choose_from_list(repos)?
+        };
+        !repos.is_empty()
+    } else {
+        prompt::confirm(
+            global,
+            "Do you want to move these repositories to ghq? [y/N]: ",
+        )?
+    };
+    if !confirmed {
+        info!("🚫 Operation cancelled.");
+        return finish_report(report).map(|_| Outcome::Cancelled);
+    }
+
+    run_moves(&repos, &args.exec, report)
+}
+
+/// 選ばれたリポジトリを移動し、ジャーナルと結果を記録する
+fn run_moves(
+    repos: &[scan::Repo],
+    exec: &ExecArgs,
+    mut report: Option<report::Report>,
+) -> Result<Outcome> {
+    let mut journal = journal::Journal::create()?;
+    debug!("📒 Recording moves in {:?}", journal.path());
+
+    let mut moved = Vec::new();
+    for repo in repos {
+        info!("🚚 Moving {:?} → {:?}", repo.src, repo.dest);
+        let result = move_repo(repo, exec, &mut journal, &mut moved);
+        if let Err(e) = &result {
+            warn!("⚠️  {:?}", e);
+        }
+        if let Some(report) = &mut report {
+            let record = match &result {
+                Ok(problems) if problems.is_empty() => report::RepoRecord::new(repo, "moved", None),
+                Ok(problems) => report::RepoRecord::new(repo, "moved", Some(problems.join("; "))),
+                Err(e) => report::RepoRecord::new(repo, "failed", Some(format!("{:#}", e))),
+            };
+            report.repository(record)?;
+        }
+    }
+
+    // 入れ子のリポジトリを先に取り出した場合に、外側と一緒に移動してしまわないよう最後に作る
+    if let Some(style) = exec.symlink {
+        for (src, dest) in &moved {
+            if !src.parent().is_some_and(|p| p.is_dir()) {
+                debug!(
+                    "🔗 {:?} no longer exists; not linking {:?}",
+                    src.parent(),
+                    src
+                );
+                continue;
+            }
+            let entry = journal.begin(journal::Action::Symlink, src, dest)?;
+            match links::create(src, dest, style) {
+                Ok(()) => {
+                    journal.finish(&entry, journal::Status::Done)?;
+                    debug!("🔗 {:?} → {:?}", src, dest);
+                }
+                Err(e) => {
+                    journal.finish(&entry, journal::Status::Failed)?;
+                    warn!("⚠️  {:?}", e);
+                }
+            }
+        }
     }
 
-    for (src, dest) in repos {
-        println!("🚚 Moving {:?} → {:?}", src, dest);
+    info!("🎉 Done!");
+    info!("↩️  To reverse this migration, run `ghq-mover undo`.");
+    finish_report(report).map(|_| Outcome::Completed)
+}
+
+/// `plan --output` で書き出した計画ファイルを、もう一度確かめてから実行する
+fn apply(global: &GlobalArgs, args: &ApplyArgs) -> Result<Outcome> {
+    let entries = planfile::read(&args.file)?;
+    let dests = destinations(global, false)?;
+    let ssh_config = Some(ssh_config::SshConfig::load());
+    info!("📝 Checking {} moves from {:?}", entries.len(), args.file);
 
-        if dest.exists() {
-            println!("⚠️  Destination {:?} already exists. Skipping.", dest);
+    let mut repos = Vec::new();
+    let mut skipped = Vec::new();
+    for entry in entries {
+        let opts = scan::Options {
+            dests: dests.clone(),
+            remotes: vec![entry.remote.clone()],
+            ssh_config: ssh_config.clone(),
+            filter: filter::Filter::default(),
+            max_depth: None,
+            one_file_system: false,
+            follow_links: false,
+        };
+        let stale = |why: &str| scan::Skipped {
+            path: entry.source.clone(),
+            reason: scan::SkipReason::Stale(why.to_string()),
+        };
+        let mut repo = match scan::inspect(&entry.source, &opts) {
+            Ok(repo) => repo,
+            Err(scan::SkipReason::OpenFailed(_)) if !entry.source.exists() => {
+                skipped.push(stale("the source no longer exists"));
+                continue;
+            }
+            Err(reason) => {
+                skipped.push(scan::Skipped {
+                    path: entry.source,
+                    reason,
+                });
+                continue;
+            }
+        };
+        if journal::head(&entry.source) != entry.head {
+            skipped.push(stale("HEAD has changed since the plan was made"));
             continue;
         }
-        if let Err(e) = fs::create_dir_all(dest.parent().unwrap()) {
-            println!(
-                "⚠️  Failed to create parent directory for {:?}: {:?}",
-                dest, e
+        repo.dest = ghq::normalize(&entry.destination)?;
+        repos.push(repo);
+    }
+
+    // 計画ファイルは手で編集されるので、移動先の重複や既存のものもここで確かめる
+    let verdicts = plan::check_moves(&repos);
+    let (repos, rejected): (Vec<_>, Vec<_>) = repos
+        .into_iter()
+        .zip(verdicts)
+        .partition(|(_, verdict)| verdict.is_ok());
+    let repos: Vec<scan::Repo> = repos.into_iter().map(|(r, _)| r).collect();
+    skipped.extend(rejected.into_iter().map(|(r, verdict)| scan::Skipped {
+        path: r.src,
+        reason: scan::SkipReason::Stale(verdict.to_string()),
+    }));
+
+    let mut report = (global.format != output::Format::Text)
+        .then(|| report::Report::new(global.format))
+        .transpose()?;
+    if let Some(report) = &mut report {
+        report.skipped(&skipped)?;
+    }
+    if repos.is_empty() {
+        print_skipped(&skipped);
+        info!("⚠️  Nothing in the plan can be moved.");
+        return finish_report(report).map(|_| Outcome::Completed);
+    }
+
+    info!("\n✅ Moves to apply:");
+    for r in &repos {
+        info!("- {:?}{}\n  → {:?}\n", r.src, kind_label(r), r.dest);
+    }
+    print_skipped(&skipped);
+    print_risky(&repos);
+
+    if !prompt::confirm(global, "Do you want to apply this plan? [y/N]: ")? {
+        info!("🚫 Operation cancelled.");
+        return finish_report(report).map(|_| Outcome::Cancelled);
+    }
+    run_moves(&repos, &args.exec, report)
+}
+
+fn finish_report(report: Option<report::Report>) -> Result<()> {
+    match report {
+        Some(report) => report.finish(),
+        None => Ok(()),
+    }
+}
+
+/// リポジトリごとに移動するかどうかを尋ねる
+fn choose_each(repos: Vec<scan::Repo>) -> Result<Vec<scan::Repo>> {
+    let total = repos.len();
+    let mut chosen = Vec::new();
+    let mut remaining = repos.into_iter().enumerate();
+    while let Some((i, mut repo)) = remaining.next() {
+        say!(
+            "\n({}/{}) {:?}{}\n  → {:?}",
+            i + 1,
+            total,
+            repo.src,
+            kind_label(&repo),
+            repo.dest
+        );
+        print_safety(&repo);
+        loop {
+            let Some(choice) = prompt::choose("Move this repository?")? else {
+                // 入力が終わったら何も移動しない
+                return Ok(Vec::new());
+            };
+            match choice {
+                prompt::Choice::Move => chosen.push(repo),
+                prompt::Choice::Skip => {}
+                prompt::Choice::MoveTo(dest) => {
+                    let dest = ghq::normalize(&dest)?;
+                    if fs::symlink_metadata(&dest).is_ok() {
+                        say!("⚠️  {:?} already exists.", dest);
+                        continue;
+                    }
+                    repo.dest = dest;
+                    chosen.push(repo);
+                }
+                prompt::Choice::All => {
+                    chosen.push(repo);
+                    chosen.extend(remaining.by_ref().map(|(_, r)| r));
+                }
+                prompt::Choice::Quit => return Ok(chosen),
+            }
+            break;
+        }
+    }
+    Ok(chosen)
+}
+
+/// チェックリストから移動するリポジトリを選ばせる
+fn choose_from_list(repos: Vec<scan::Repo>) -> Result<Vec<scan::Repo>> {
+    let items: Vec<String> = repos
+        .iter()
+        .map(|r| format!("{:?}{} → {:?}", r.src, kind_label(r), r.dest))
+        .collect();
+    let mut checked = vec![true; repos.len()];
+    if !prompt::checklist(&items, &mut checked)? {
+        return Ok(Vec::new());
+    }
+    Ok(repos
+        .into_iter()
+        .zip(checked)
+        .filter_map(|(r, checked)| checked.then_some(r))
+        .collect())
+}
+
+/// 1 つのリポジトリを移動し、移動で壊れる参照を直す
+///
+/// 移動できたもの (ワークツリーを含む) は `moved` に追加する。
+/// 移動した後の修復で起きた問題は、移動を失敗にはせず警告として返す。
+fn move_repo(
+    repo: &scan::Repo,
+    exec: &ExecArgs,
+    journal: &mut journal::Journal,
+    moved: &mut Vec<(PathBuf, PathBuf)>,
+) -> Result<Vec<String>> {
+    let (src, dest) = (&repo.src, &repo.dest);
+    if dest.exists() {
+        bail!("Destination {:?} already exists. Skipping.", dest);
+    }
+    fs::create_dir_all(dest.parent().unwrap())
+        .with_context(|| format!("Failed to create parent directory for {:?}", dest))?;
+    let entry = journal.begin(journal::Action::Repo, src, dest)?;
+    match mover::move_dir(src, dest).context("Failed to move directory") {
+        Ok(mover::Method::Renamed) => {}
+        Ok(mover::Method::Copied) => {
+            debug!("📦 Copied across filesystems and removed {:?}", src)
+        }
+        Err(e) => {
+            journal.finish(&entry, journal::Status::Failed)?;
+            return Err(e);
+        }
+    }
+    journal.finish(&entry, journal::Status::Done)?;
+    moved.push((src.clone(), dest.clone()));
+
+    let mut problems = Vec::new();
+    match repair::submodules(src, dest) {
+        Ok(broken) => {
+            for path in broken {
+                problems.push(format!(
+                    "Submodule {:?} could not be opened after the move",
+                    path
+                ));
+            }
+        }
+        Err(e) => problems.push(format!("Failed to update submodule links: {:#}", e)),
+    }
+
+    let worktrees = match repair::worktrees(src, dest) {
+        Ok(worktrees) => worktrees,
+        Err(e) => {
+            problems.push(format!("Failed to update worktree links: {:#}", e));
+            Vec::new()
+        }
+    };
+    for worktree in &worktrees {
+        if !exec.relocate_worktrees || worktree.path.starts_with(dest) {
+            debug!(
+                "🌿 Updated worktree {:?} at {:?}",
+                worktree.name, worktree.path
             );
             continue;
         }
-        if let Err(e) = fs::rename(&src, &dest)
-            .with_context(|| format!("Failed to move {:?} to {:?}", src, dest))
-        {
-            println!("⚠️  Failed to move directory {:?}", e);
+        let mut to = dest.clone().into_os_string();
+        to.push(".worktrees");
+        let to = PathBuf::from(to).join(&worktree.name);
+        info!("🌿 Moving worktree {:?} → {:?}", worktree.path, to);
+        let action = journal::Action::Worktree {
+            repo: dest.clone(),
+            name: worktree.name.clone(),
+        };
+        let entry = journal.begin(action, &worktree.path, &to)?;
+        match repair::relocate_worktree(dest, worktree, &to) {
+            Ok(()) => {
+                journal.finish(&entry, journal::Status::Done)?;
+                moved.push((worktree.path.clone(), to));
+            }
+            Err(e) => {
+                journal.finish(&entry, journal::Status::Failed)?;
+                problems.push(format!(
+                    "Failed to move worktree {:?}: {:#}",
+                    worktree.path, e
+                ));
+            }
         }
     }
+    for problem in &problems {
+        warn!("⚠️  {}", problem);
+    }
+    Ok(problems)
+}
 
-    println!("🎉 Done!");
-    Ok(())
+/// ジャーナルを逆順にたどり、移動したものを元の場所に戻す
+fn undo(global: &GlobalArgs, args: &UndoArgs) -> Result<Outcome> {
+    let path = match &args.journal {
+        Some(path) => path.clone(),
+        None => journal::latest()?.context("No journal found; nothing has been moved yet")?,
+    };
+    info!("📒 Reading {:?}", path);
+    let entries = journal::latest_entries(journal::read(&path)?);
+
+    for e in entries
+        .iter()
+        .filter(|e| e.status == journal::Status::Pending)
+    {
+        warn!(
+            "⚠️  The move {:?} → {:?} was interrupted; check both locations by hand",
+            e.src, e.dest
+        );
+    }
+    let done: Vec<_> = entries
+        .into_iter()
+        .filter(|e| e.status == journal::Status::Done)
+        .rev()
+        .collect();
+    if done.is_empty() {
+        info!("✅ Nothing to undo.");
+        return Ok(Outcome::Completed);
+    }
+
+    info!("\n↩️  Moves to undo:");
+    for e in &done {
+        match e.action {
+            journal::Action::Symlink => info!("- 🔗 remove symlink {:?}\n", e.src),
+            _ => info!("- {:?}\n  → {:?}\n", e.dest, e.src),
+        }
+    }
+    if !prompt::confirm(global, "Do you want to move these back? [y/N]: ")? {
+        info!("🚫 Operation cancelled.");
+        return Ok(Outcome::Cancelled);
+    }
+
+    let mut journal = journal::Journal::open(&path)?;
+    for entry in &done {
+        match entry.action {
+            journal::Action::Symlink => info!("🔗 Removing symlink {:?}", entry.src),
+            _ => info!("↩️  Moving {:?} → {:?}", entry.dest, entry.src),
+        }
+        match undo_entry(entry) {
+            Ok(()) => journal.finish(entry, journal::Status::Undone)?,
+            Err(e) => warn!("⚠️  {:?}", e),
+        }
+    }
+
+    info!("🎉 Done!");
+    Ok(Outcome::Completed)
 }
 
-/// 確認用プロンプト
-fn confirm(prompt: &str) -> Result<bool> {
-    print!("{}", prompt);
-    io::stdout().flush()?;
+fn undo_entry(entry: &journal::Entry) -> Result<()> {
+    let (src, dest) = (&entry.src, &entry.dest);
+    match &entry.action {
+        journal::Action::Repo => {
+            check_unchanged(entry)?;
+            if let Some(parent) = src.parent() {
+                fs::create_dir_all(parent)
+                    .with_context(|| format!("Failed to create parent directory for {:?}", src))?;
+            }
+            mover::move_dir(dest, src).context("Failed to move directory")?;
+            repair::submodules(dest, src).context("Failed to update submodule links")?;
+            repair::worktrees(dest, src).context("Failed to update worktree links")?;
+        }
+        journal::Action::Worktree { repo, name } => {
+            check_unchanged(entry)?;
+            let worktree = repair::Worktree {
+                name: name.clone(),
+                path: dest.clone(),
+            };
+            repair::relocate_worktree(repo, &worktree, src).context("Failed to move worktree")?;
+        }
+        journal::Action::Symlink => {
+            if links::remove(src, dest)? == links::LinkState::Replaced {
+                bail!("{:?} has been replaced. Skipping.", src);
+            }
+        }
+    }
+    Ok(())
+}
 
-    let mut input = String::new();
-    io::stdin().read_line(&mut input)?;
-    Ok(matches!(input.trim().to_lowercase().as_str(), "y" | "yes"))
+/// 移動後に移動先が変更されていないか確かめる
+fn check_unchanged(entry: &journal::Entry) -> Result<()> {
+    let (src, dest) = (&entry.src, &entry.dest);
+    if !dest.exists() {
+        bail!("{:?} no longer exists. Skipping.", dest);
+    }
+    if fs::symlink_metadata(src).is_ok() {
+        bail!("{:?} already exists again. Skipping.", src);
+    }
+    let head = journal::head(dest);
+    if head != entry.head {
+        bail!(
+            "HEAD of {:?} has changed since the move ({} → {}). Skipping.",
+            dest,
+            entry.head.as_deref().unwrap_or("none"),
+            head.as_deref().unwrap_or("none")
+        );
+    }
+    Ok(())
 }
 
-fn find_git_repos(base: &Path) -> Result<Vec<(PathBuf, PathBuf)>> {
-    let mut repos = Vec::new();
+/// `--symlink` で残したリンクの一覧を表示し、`--clean` なら削除する
+fn links(global: &GlobalArgs, args: &LinksArgs) -> Result<Outcome> {
+    let mut found = Vec::new();
+    for path in journal::all()? {
+        for entry in journal::latest_entries(journal::read(&path)?) {
+            if entry.action == journal::Action::Symlink && entry.status == journal::Status::Done {
+                let state = links::state(&entry.src, &entry.dest);
+                found.push((path.clone(), entry, state));
+            }
+        }
+    }
+    if found.is_empty() {
+        info!("✅ No compatibility symlinks are left.");
+        return Ok(Outcome::Completed);
+    }
 
-    let ghq_dir = if let Ok(ghq_root) = std::env::var("GHQ_ROOT") {
-        PathBuf::from(ghq_root)
-    } else {
-        let mut ghq_dir = std::env::home_dir().context("Failed to find home directory")?;
-        ghq_dir.push("ghq");
-        ghq_dir
-    };
+    for (_, entry, state) in &found {
+        say!("🔗 {:?}\n  → {:?}\n  {}\n", entry.src, entry.dest, state);
+    }
+    if !args.clean {
+        return Ok(Outcome::Completed);
+    }
+    if !prompt::confirm(global, "Do you want to remove these symlinks? [y/N]: ")? {
+        info!("🚫 Operation cancelled.");
+        return Ok(Outcome::Cancelled);
+    }
 
-    let mut it = WalkDir::new(base)
-        .into_iter()
-        .filter_entry(|e| e.file_type().is_dir());
-    loop {
-        let entry = match it.next() {
-            None => break,
-            Some(Err(err)) => continue,
-            Some(Ok(entry)) => entry,
-        };
-        if entry.file_type().is_dir() && entry.file_name() == ".git" {
-            it.skip_current_dir();
-
-            let git_dir = entry.path();
-            let repo_root = git_dir.parent().unwrap_or(git_dir);
-            if let Ok(repo) = Repository::open(repo_root)
-                && let Ok(remote) = repo.find_remote("origin")
-                && let Some(url) = remote.url()
-            {
-                let Ok(git_url) = git_url_parse::GitUrl::parse(url) else {
-                    continue;
-                };
-                let Some((owner, repo)) = git_url.path().split_once('/') else {
-                    continue;
-                };
-
-                let mut target_path = ghq_dir.clone();
-                target_path.push(git_url.host().context("Invalid Git URL")?);
-                target_path.push(owner);
-                target_path.push(repo.trim_end_matches(".git"));
-
-                repos.push((repo_root.to_path_buf(), target_path));
+    for (path, entry, _) in &found {
+        match links::remove(&entry.src, &entry.dest) {
+            Ok(links::LinkState::Replaced) => {}
+            Ok(_) => {
+                debug!("🧹 Removed {:?}", entry.src);
+                journal::Journal::open(path)?.finish(entry, journal::Status::Undone)?;
             }
+            Err(e) => warn!("⚠️  {:?}", e),
+        }
+    }
+    info!("🎉 Done!");
+    Ok(Outcome::Completed)
+}
+
+/// 実行環境の確認
+fn doctor(global: &GlobalArgs) -> Result<()> {
+    let (major, minor, rev) = git2::Version::get().libgit2_version();
+    say!("ℹ️  libgit2 {}.{}.{}", major, minor, rev);
+
+    match std::env::home_dir() {
+        Some(home) => say!("✅ Home directory: {:?}", home),
+        None => say!("⚠️  Home directory could not be determined"),
+    }
+
+    let roots = ghq::Roots::load()?;
+    let source = match roots.source {
+        ghq::RootSource::Env => "$GHQ_ROOT",
+        ghq::RootSource::GitConfig => "git config ghq.root",
+        ghq::RootSource::Default => "default",
+    };
+    say!("ℹ️  ghq roots (from {}):", source);
+    for (i, root) in roots.roots.iter().enumerate() {
+        let label = if i == 0 { " (primary)" } else { "" };
+        if root.is_dir() {
+            say!("✅ {:?}{}", root, label);
+        } else if root.exists() {
+            say!("⚠️  {:?}{} is not a directory", root, label);
+        } else {
+            say!("⚠️  {:?}{} does not exist yet", root, label);
         }
     }
+    if let Some(root) = &global.root {
+        say!("ℹ️  --root overrides the destination with {:?}", root);
+    }
+    for url_root in ghq::UrlRoot::load_all()? {
+        say!("ℹ️  {} → {:?}", url_root.prefix, url_root.root);
+    }
+    match journal::dir() {
+        Ok(dir) => say!("ℹ️  Journal directory: {:?}", dir),
+        Err(e) => say!("⚠️  {}", e),
+    }
+    Ok(())
+}
+
+/// 移動先の ghq ルートを決める (`interactive` なら複数ルートから選ばせる)
+fn ghq_root(global: &GlobalArgs, interactive: bool) -> Result<PathBuf> {
+    if let Some(root) = &global.root {
+        return ghq::normalize(root);
+    }
+
+    let roots = ghq::Roots::load()?;
+    if !interactive || roots.roots.len() < 2 {
+        return Ok(roots.primary().to_path_buf());
+    }
+
+    let items: Vec<String> = roots.roots.iter().map(|r| format!("{:?}", r)).collect();
+    let i = prompt::select(global, "📁 Multiple ghq roots are configured:", &items)?;
+    Ok(roots.roots[i].clone())
+}
 
-    Ok(repos)
+fn destinations(global: &GlobalArgs, interactive: bool) -> Result<ghq::Destinations> {
+    Ok(ghq::Destinations {
+        root: ghq_root(global, interactive)?,
+        url_roots: ghq::UrlRoot::load_all()?,
+    })
 }
@@ -0,0 +1,299 @@
+use std::{
+    fs::{self, File},
+    io::{self, Read},
+    path::Path,
+};
+
+use anyhow::{Context, Result, bail};
+use filetime::FileTime;
+use walkdir::WalkDir;
+
+/// 実際に使われた移動方法
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    Renamed,
+    /// 別のファイルシステムへコピーして検証後に元を削除した
+    Copied,
+}
+
+/// `src` を `dest` に移動する
+///
+/// `fs::rename` がファイルシステムをまたぐために失敗した場合は、コピー・検証・削除で代替する。
+pub fn move_dir(src: &Path, dest: &Path) -> Result<Method> {
+    match fs::rename(src, dest) {
+        Ok(()) => Ok(Method::Renamed),
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+            debug!("↪️  {:?} is on another filesystem, copying instead", dest);
+            copy_verify_delete(src, dest)?;
+            Ok(Method::Copied)
+        }
+        Err(e) => Err(e).with_context(|| format!("Failed to move {:?} to {:?}", src, dest)),
+    }
+}
+
+fn copy_verify_delete(src: &Path, dest: &Path) -> Result<()> {
+    transfer(src, dest, verify_tree)
+}
+
+/// コピーして `verify` で確かめ、問題がなければ元を削除する (失敗したらコピー先を消す)
+fn transfer(
+    src: &Path,
+    dest: &Path,
+    verify: impl FnOnce(&Path, &Path) -> Result<()>,
+) -> Result<()> {
+    let copied = copy_tree(src, dest).and_then(|()| verify(src, dest));
+    if let Err(e) = copied {
+        if let Err(cleanup) = remove_tree(dest) {
+            warn!(
+                "⚠️  Failed to clean up partial copy {:?}: {:?}",
+                dest, cleanup
+            );
+        }
+        return Err(e.context(format!("Failed to copy {:?} to {:?}", src, dest)));
+    }
+
+    remove_tree(src).with_context(|| {
+        format!(
+            "Copied {:?} to {:?}, but failed to remove the source",
+            src, dest
+        )
+    })
+}
+
+/// パーミッション・シンボリックリンク・タイムスタンプを保ったままツリーをコピーする
+fn copy_tree(src: &Path, dest: &Path) -> Result<()> {
+    for entry in WalkDir::new(src) {
+        let entry = entry?;
+        let from = entry.path();
+        let to = dest.join(from.strip_prefix(src)?);
+        let file_type = entry.file_type();
+
+        if file_type.is_dir() {
+            fs::create_dir(&to).with_context(|| format!("Failed to create {:?}", to))?;
+        } else if file_type.is_symlink() {
+            copy_symlink(from, &to)?;
+        } else if file_type.is_file() {
+            fs::copy(from, &to).with_context(|| format!("Failed to copy {:?}", from))?;
+            copy_times(&entry.metadata()?, &to)?;
+        } else {
+            bail!("Unsupported file type at {:?}", from);
+        }
+    }
+
+    // 中身を書き込むとディレクトリの mtime が変わるので、ディレクトリの属性は最後に設定する
+    for entry in WalkDir::new(src).contents_first(true) {
+        let entry = entry?;
+        if entry.file_type().is_dir() {
+            let to = dest.join(entry.path().strip_prefix(src)?);
+            let meta = entry.metadata()?;
+            fs::set_permissions(&to, meta.permissions())?;
+            copy_times(&meta, &to)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn copy_symlink(from: &Path, to: &Path) -> Result<()> {
+    let target = fs::read_link(from)?;
+    std::os::unix::fs::symlink(&target, to)
+        .with_context(|| format!("Failed to create symlink {:?}", to))?;
+
+    let meta = fs::symlink_metadata(from)?;
+    filetime::set_symlink_file_times(
+        to,
+        FileTime::from_last_access_time(&meta),
+        FileTime::from_last_modification_time(&meta),
+    )?;
+    Ok(())
+}
+
+#[cfg(windows)]
+fn copy_symlink(from: &Path, to: &Path) -> Result<()> {
+    let target = fs::read_link(from)?;
+    let result = if fs::metadata(from).is_ok_and(|m| m.is_dir()) {
+        std::os::windows::fs::symlink_dir(&target, to)
+    } else {
+        std::os::windows::fs::symlink_file(&target, to)
+    };
+    result.with_context(|| format!("Failed to create symlink {:?}", to))
+}
+
+fn copy_times(meta: &fs::Metadata, to: &Path) -> Result<()> {
+    filetime::set_file_times(
+        to,
+        FileTime::from_last_access_time(meta),
+        FileTime::from_last_modification_time(meta),
+    )
+    .with_context(|| format!("Failed to set timestamps of {:?}", to))
+}
+
+/// コピー先が元と同じ内容・属性であることを確認する
+fn verify_tree(src: &Path, dest: &Path) -> Result<()> {
+    for entry in WalkDir::new(src) {
+        let entry = entry?;
+        let from = entry.path();
+        let to = dest.join(from.strip_prefix(src)?);
+
+        let from_meta = entry.metadata()?;
+        let to_meta =
+            fs::symlink_metadata(&to).with_context(|| format!("{:?} was not copied", from))?;
+
+        if from_meta.file_type() != to_meta.file_type() {
+            bail!("File type of {:?} differs after copy", to);
+        }
+        if from_meta.file_type().is_symlink() {
+            if fs::read_link(from)? != fs::read_link(&to)? {
+                bail!("Symlink target of {:?} differs after copy", to);
+            }
+            continue;
+        }
+        if from_meta.permissions() != to_meta.permissions() {
+            bail!("Permissions of {:?} differ after copy", to);
+        }
+        if from_meta.is_file() {
+            if FileTime::from_last_modification_time(&from_meta)
+                != FileTime::from_last_modification_time(&to_meta)
+            {
+                bail!("Modification time of {:?} differs after copy", to);
+            }
+            if from_meta.len() != to_meta.len() || !same_contents(from, &to)? {
+                bail!("Contents of {:?} differ after copy", to);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn same_contents(a: &Path, b: &Path) -> Result<bool> {
+    let mut a = File::open(a)?;
+    let mut b = File::open(b)?;
+    let mut buf_a = vec![0; 64 * 1024];
+    let mut buf_b = vec![0; 64 * 1024];
+
+    loop {
+        let n = a.read(&mut buf_a)?;
+        if n == 0 {
+            return Ok(b.read(&mut buf_b)? == 0);
+        }
+        b.read_exact(&mut buf_b[..n])?;
+        if buf_a[..n] != buf_b[..n] {
+            return Ok(false);
+        }
+    }
+}
+
+/// 読み取り専用のディレクトリ (git のオブジェクトなど) も含めて削除する
+fn remove_tree(path: &Path) -> Result<()> {
+    if fs::symlink_metadata(path).is_err() {
+        return Ok(());
+    }
+    if fs::remove_dir_all(path).is_ok() {
+        return Ok(());
+    }
+
+    for entry in WalkDir::new(path).into_iter().flatten() {
+        if entry.file_type().is_dir() {
+            let mut perms = entry.metadata()?.permissions();
+            #[allow(clippy::permissions_set_readonly_false)]
+            perms.set_readonly(false);
+            fs::set_permissions(entry.path(), perms)?;
+        }
+    }
+    fs::remove_dir_all(path).with_context(|| format!("Failed to remove {:?}", path))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::{
+        fs,
+        os::unix::fs::{PermissionsExt, symlink},
+        path::Path,
+    };
+
+    use filetime::FileTime;
+
+    use super::{copy_verify_delete, transfer, verify_tree};
+
+    fn mode(path: &Path) -> u32 {
+        fs::symlink_metadata(path).unwrap().permissions().mode() & 0o777
+    }
+
+    fn mtime(path: &Path) -> FileTime {
+        FileTime::from_last_modification_time(&fs::metadata(path).unwrap())
+    }
+
+    /// 実行ファイル、読み取り専用のファイルとディレクトリ、相対リンクを含むツリーを作る
+    fn make_tree(root: &Path) {
+        fs::create_dir_all(root.join("bin")).unwrap();
+        fs::write(root.join("bin/run"), "#!/bin/sh\n").unwrap();
+        fs::set_permissions(root.join("bin/run"), fs::Permissions::from_mode(0o755)).unwrap();
+        fs::write(root.join("readonly"), "keep").unwrap();
+        fs::set_permissions(root.join("readonly"), fs::Permissions::from_mode(0o444)).unwrap();
+        symlink("bin/run", root.join("link")).unwrap();
+
+        fs::create_dir(root.join("objects")).unwrap();
+        fs::write(root.join("objects/pack"), "data").unwrap();
+        fs::set_permissions(root.join("objects/pack"), fs::Permissions::from_mode(0o444)).unwrap();
+        fs::set_permissions(root.join("objects"), fs::Permissions::from_mode(0o555)).unwrap();
+
+        let old = FileTime::from_unix_time(1_000_000_000, 0);
+        filetime::set_file_mtime(root.join("bin/run"), old).unwrap();
+    }
+
+    #[test]
+    fn copies_attributes_and_removes_the_source() {
+        let src_dir = tempfile::tempdir().unwrap();
+        let dest_dir = tempfile::tempdir().unwrap();
+        let src = src_dir.path().join("repo");
+        let dest = dest_dir.path().join("repo");
+        make_tree(&src);
+        let run_mtime = mtime(&src.join("bin/run"));
+        let pack_mtime = mtime(&src.join("objects/pack"));
+
+        copy_verify_delete(&src, &dest).unwrap();
+
+        assert!(fs::symlink_metadata(&src).is_err());
+        assert_eq!(mode(&dest.join("bin/run")), 0o755);
+        assert_eq!(mode(&dest.join("readonly")), 0o444);
+        assert_eq!(mode(&dest.join("objects")), 0o555);
+        assert_eq!(mode(&dest.join("objects/pack")), 0o444);
+        assert_eq!(
+            fs::read_link(dest.join("link")).unwrap(),
+            Path::new("bin/run")
+        );
+        assert_eq!(
+            fs::read_to_string(dest.join("link")).unwrap(),
+            "#!/bin/sh\n"
+        );
+        assert_eq!(mtime(&dest.join("bin/run")), run_mtime);
+        assert_eq!(mtime(&dest.join("objects/pack")), pack_mtime);
+    }
+
+    #[test]
+    fn keeps_the_source_when_verification_fails() {
+        let src_dir = tempfile::tempdir().unwrap();
+        let dest_dir = tempfile::tempdir().unwrap();
+        let src = src_dir.path().join("repo");
+        let dest = dest_dir.path().join("repo");
+        make_tree(&src);
+
+        // コピーと検証の間にコピー先が書き換えられた
+        let result = transfer(&src, &dest, |src, dest| {
+            fs::write(dest.join("bin/run"), "#!/bin/sh\nexit 1\n").unwrap();
+            verify_tree(src, dest)
+        });
+
+        assert!(result.is_err());
+        assert!(fs::symlink_metadata(&dest).is_err());
+        assert_eq!(
+            fs::read_to_string(src.join("bin/run")).unwrap(),
+            "#!/bin/sh\n"
+        );
+        assert_eq!(
+            fs::read_to_string(src.join("objects/pack")).unwrap(),
+            "data"
+        );
+        assert_eq!(mode(&src.join("objects")), 0o555);
+    }
+}
@@ -0,0 +1,86 @@
+use std::{
+    fmt,
+    io::{self, Write},
+    sync::atomic::{AtomicBool, AtomicI8, Ordering},
+};
+
+static VERBOSITY: AtomicI8 = AtomicI8::new(0);
+static MACHINE_READABLE: AtomicBool = AtomicBool::new(false);
+
+/// 結果の出力形式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    /// Human-readable text
+    #[default]
+    Text,
+    /// A single JSON document once everything is done
+    Json,
+    /// One JSON object per line as soon as each result is known
+    Ndjson,
+}
+
+/// -1: quiet, 0: normal, 1 以上: verbose
+pub fn set_verbosity(level: i8) {
+    VERBOSITY.store(level, Ordering::Relaxed);
+}
+
+pub fn verbosity() -> i8 {
+    VERBOSITY.load(Ordering::Relaxed)
+}
+
+/// 機械向けの形式では、人向けの表示を stdout に混ぜないよう stderr に出す
+pub fn set_format(format: Format) {
+    MACHINE_READABLE.store(format != Format::Text, Ordering::Relaxed);
+}
+
+/// 人向けの表示を 1 行出力する
+pub fn human(args: fmt::Arguments<'_>) {
+    if MACHINE_READABLE.load(Ordering::Relaxed) {
+        eprintln!("{}", args);
+    } else {
+        println!("{}", args);
+    }
+}
+
+/// 改行せずに出力する (入力を促すため)
+pub fn human_prompt(prompt: &str) -> io::Result<()> {
+    if MACHINE_READABLE.load(Ordering::Relaxed) {
+        eprint!("{}", prompt);
+        io::stderr().flush()
+    } else {
+        print!("{}", prompt);
+        io::stdout().flush()
+    }
+}
+
+/// 常に表示する人向けの出力
+macro_rules! say {
+    ($($arg:tt)*) => {
+        $crate::output::human(format_args!($($arg)*))
+    };
+}
+
+/// 通常の進捗表示 (`--quiet` で抑制)
+macro_rules! info {
+    ($($arg:tt)*) => {
+        if $crate::output::verbosity() >= 0 {
+            say!($($arg)*)
+        }
+    };
+}
+
+/// 詳細表示 (`--verbose` のときのみ)
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        if $crate::output::verbosity() >= 1 {
+            say!($($arg)*)
+        }
+    };
+}
+
+/// 警告 (常に表示)
+macro_rules! warn {
+    ($($arg:tt)*) => {
+        say!($($arg)*)
+    };
+}
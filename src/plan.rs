@@ -0,0 +1,482 @@
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+    ffi::OsString,
+    fmt, fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use git2::Repository;
+
+use crate::scan::{Repo, Scan, SkipReason, Skipped};
+
+/// 入れ子になったリポジトリの扱い
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum NestedMode {
+    /// Move only the outermost repository; nested ones travel inside it
+    Outer,
+    /// Extract nested repositories to their own destinations, then move the outer one
+    Both,
+    /// Extract nested repositories and leave the outermost one in place
+    Inner,
+}
+
+/// 同じ移動先になるリポジトリの扱い
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ConflictStrategy {
+    /// Leave all of them in place
+    Skip,
+    /// Move the one whose HEAD has the most recent commit and leave the others in place
+    Newest,
+    /// Move the one with the most branches and tags and leave the others in place
+    MostRefs,
+    /// Move all of them, adding a numeric suffix to the destination of all but the newest
+    Suffix,
+}
+
+/// 同じ移動先になるリポジトリのまとまり
+#[derive(Debug, Clone)]
+pub struct Conflict {
+    pub dest: PathBuf,
+    /// パス順
+    pub candidates: Vec<Candidate>,
+}
+
+/// どれを移動するかの判断材料
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub src: PathBuf,
+    /// HEAD のコミット日時 (UNIX 時間)
+    pub last_commit: Option<i64>,
+    /// ブランチやタグなどの参照の数
+    pub refs: usize,
+}
+
+impl Candidate {
+    fn new(src: &Path) -> Self {
+        let repo = Repository::open(src).ok();
+        let last_commit = repo
+            .as_ref()
+            .and_then(|r| r.head().ok()?.peel_to_commit().ok())
+            .map(|c| c.time().seconds());
+        let refs = repo
+            .as_ref()
+            .and_then(|r| r.references().ok())
+            .map_or(0, |refs| refs.flatten().count());
+        Candidate {
+            src: src.to_path_buf(),
+            last_commit,
+            refs,
+        }
+    }
+}
+
+impl fmt::Display for Candidate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.last_commit {
+            Some(time) => {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |d| d.as_secs() as i64);
+                match (now - time).max(0) / 86400 {
+                    0 => write!(f, "last commit today")?,
+                    1 => write!(f, "last commit 1 day ago")?,
+                    days => write!(f, "last commit {} days ago", days)?,
+                }
+            }
+            None => write!(f, "no commits")?,
+        }
+        write!(f, ", {} refs", self.refs)
+    }
+}
+
+/// 移動計画の各エントリに対する判定結果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    Ok,
+    SourceMissing,
+    DestinationExists,
+    /// 同じ移動先を持つ別のリポジトリがある
+    Conflict(PathBuf),
+    ParentNotCreatable(PathBuf),
+    SourceNotMovable(PathBuf),
+    /// 移動可能だが、別のファイルシステムなのでコピーになる
+    CrossDevice,
+}
+
+impl Verdict {
+    pub fn is_ok(&self) -> bool {
+        matches!(self, Verdict::Ok | Verdict::CrossDevice)
+    }
+
+    /// 機械向けの出力で使う識別子
+    pub fn code(&self) -> &'static str {
+        match self {
+            Verdict::Ok => "ok",
+            Verdict::SourceMissing => "source_missing",
+            Verdict::DestinationExists => "destination_exists",
+            Verdict::Conflict(_) => "conflict",
+            Verdict::ParentNotCreatable(_) => "parent_not_creatable",
+            Verdict::SourceNotMovable(_) => "source_not_movable",
+            Verdict::CrossDevice => "cross_device",
+        }
+    }
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Verdict::Ok => write!(f, "ok"),
+            Verdict::SourceMissing => write!(f, "source no longer exists"),
+            Verdict::DestinationExists => write!(f, "destination already exists"),
+            Verdict::Conflict(other) => {
+                write!(f, "destination is also the target of {:?}", other)
+            }
+            Verdict::ParentNotCreatable(dir) => {
+                write!(
+                    f,
+                    "cannot create parent directory ({:?} is not writable)",
+                    dir
+                )
+            }
+            Verdict::SourceNotMovable(dir) => {
+                write!(f, "cannot remove source ({:?} is not writable)", dir)
+            }
+            Verdict::CrossDevice => write!(
+                f,
+                "ok (different filesystem: will copy, verify, then delete the source)"
+            ),
+        }
+    }
+}
+
+/// ファイルシステムに触れずに各 (src, dest) の移動可否を判定する
+pub fn check_moves(repos: &[Repo]) -> Vec<Verdict> {
+    let mut first_by_dest: HashMap<&Path, &Path> = HashMap::new();
+    for r in repos {
+        first_by_dest.entry(&r.dest).or_insert(&r.src);
+    }
+
+    repos
+        .iter()
+        .map(|r| match first_by_dest.get(r.dest.as_path()) {
+            Some(first) if *first != r.src.as_path() => Verdict::Conflict(first.to_path_buf()),
+            _ => check_move(&r.src, &r.dest),
+        })
+        .collect()
+}
+
+fn check_move(src: &Path, dest: &Path) -> Verdict {
+    let Ok(src_meta) = fs::symlink_metadata(src) else {
+        return Verdict::SourceMissing;
+    };
+    if fs::symlink_metadata(dest).is_ok() {
+        return Verdict::DestinationExists;
+    }
+
+    if let Some(src_parent) = src.parent()
+        && !is_writable(src_parent)
+    {
+        return Verdict::SourceNotMovable(src_parent.to_path_buf());
+    }
+
+    let Some(ancestor) = dest.ancestors().skip(1).find(|p| p.exists()) else {
+        return Verdict::ParentNotCreatable(dest.to_path_buf());
+    };
+    if !ancestor.is_dir() || !is_writable(ancestor) {
+        return Verdict::ParentNotCreatable(ancestor.to_path_buf());
+    }
+
+    if let Ok(ancestor_meta) = fs::metadata(ancestor)
+        && !same_device(&src_meta, &ancestor_meta)
+    {
+        return Verdict::CrossDevice;
+    }
+
+    Verdict::Ok
+}
+
+#[cfg(unix)]
+fn is_writable(path: &Path) -> bool {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+    let Ok(c_path) = CString::new(path.as_os_str().as_bytes()) else {
+        return false;
+    };
+    unsafe { libc::access(c_path.as_ptr(), libc::W_OK) == 0 }
+}
+
+#[cfg(not(unix))]
+fn is_writable(path: &Path) -> bool {
+    fs::metadata(path)
+        .map(|m| !m.permissions().readonly())
+        .unwrap_or(false)
+}
+
+#[cfg(unix)]
+fn same_device(a: &fs::Metadata, b: &fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    a.dev() == b.dev()
+}
+
+#[cfg(not(unix))]
+fn same_device(_a: &fs::Metadata, _b: &fs::Metadata) -> bool {
+    true
+}
+
+/// 入れ子のリポジトリに `nested_in` を設定し、入れ子の数を返す
+pub fn mark_nested(repos: &mut [Repo]) -> usize {
+    let srcs: HashSet<PathBuf> = repos.iter().map(|r| r.src.clone()).collect();
+    let mut count = 0;
+    for r in repos.iter_mut() {
+        r.nested_in = r
+            .src
+            .ancestors()
+            .skip(1)
+            .find(|a| srcs.contains(*a))
+            .map(Path::to_path_buf);
+        count += usize::from(r.nested_in.is_some());
+    }
+    count
+}
+
+/// 入れ子の扱いに従って移動対象を絞り込み、移動の順序を決める
+///
+/// 内側を取り出す場合は、外側より先に (深いものから順に) 移動する。
+pub fn resolve_nesting(scan: &mut Scan, mode: NestedMode) {
+    let outers: HashSet<PathBuf> = scan
+        .repos
+        .iter()
+        .filter_map(|r| r.nested_in.clone())
+        .collect();
+    if outers.is_empty() {
+        return;
+    }
+
+    let repos = std::mem::take(&mut scan.repos);
+    for r in repos {
+        let reason = match (mode, &r.nested_in) {
+            (NestedMode::Outer, Some(outer)) => Some(SkipReason::NestedIn(outer.clone())),
+            (NestedMode::Inner, None) if outers.contains(&r.src) => {
+                Some(SkipReason::ContainsNested)
+            }
+            _ => None,
+        };
+        match reason {
+            Some(reason) => scan.skipped.push(Skipped {
+                path: r.src,
+                reason,
+            }),
+            None => scan.repos.push(r),
+        }
+    }
+
+    if mode != NestedMode::Outer {
+        scan.repos
+            .sort_by_key(|r| Reverse(r.src.components().count()));
+    }
+}
+
+/// 同じ移動先になるリポジトリをまとめる
+pub fn find_conflicts(repos: &[Repo]) -> Vec<Conflict> {
+    let mut by_dest: HashMap<&Path, Vec<&Path>> = HashMap::new();
+    for r in repos {
+        by_dest.entry(&r.dest).or_default().push(&r.src);
+    }
+
+    let mut seen = HashSet::new();
+    repos
+        .iter()
+        .filter(|r| by_dest[r.dest.as_path()].len() > 1 && seen.insert(&r.dest))
+        .map(|r| Conflict {
+            dest: r.dest.clone(),
+            candidates: {
+                let mut srcs = by_dest[r.dest.as_path()].clone();
+                srcs.sort();
+                srcs.into_iter().map(Candidate::new).collect()
+            },
+        })
+        .collect()
+}
+
+/// 移動先の重複を解消する
+///
+/// 移動しないものは `skipped` に移し、`Suffix` では残りの移動先に `-2`, `-3`, ... を付ける。
+pub fn resolve_conflicts(scan: &mut Scan, conflicts: &[Conflict], strategy: ConflictStrategy) {
+    let mut skipped: HashMap<PathBuf, SkipReason> = HashMap::new();
+    let mut renamed: HashMap<PathBuf, PathBuf> = HashMap::new();
+    let mut taken: HashSet<PathBuf> = scan.repos.iter().map(|r| r.dest.clone()).collect();
+
+    for conflict in conflicts {
+        // 同点ならパス順で先のもの (`--nested` による移動順の並べ替えに左右されないように)
+        let newest = conflict
+            .candidates
+            .iter()
+            .min_by_key(|c| (Reverse(c.last_commit), &c.src));
+        let kept = match strategy {
+            ConflictStrategy::Skip => None,
+            ConflictStrategy::Newest | ConflictStrategy::Suffix => newest,
+            ConflictStrategy::MostRefs => conflict
+                .candidates
+                .iter()
+                .min_by_key(|c| (Reverse(c.refs), &c.src)),
+        };
+        for c in &conflict.candidates {
+            let Some(kept) = kept else {
+                skipped.insert(c.src.clone(), SkipReason::Conflict(conflict.dest.clone()));
+                continue;
+            };
+            if c.src == kept.src {
+                continue;
+            }
+            if strategy == ConflictStrategy::Suffix {
+                let dest = with_suffix(&conflict.dest, &taken);
+                taken.insert(dest.clone());
+                renamed.insert(c.src.clone(), dest);
+            } else {
+                skipped.insert(c.src.clone(), SkipReason::Superseded(kept.src.clone()));
+            }
+        }
+    }
+
+    let repos = std::mem::take(&mut scan.repos);
+    for mut r in repos {
+        if let Some(reason) = skipped.remove(&r.src) {
+            scan.skipped.push(Skipped {
+                path: r.src,
+                reason,
+            });
+            continue;
+        }
+        if let Some(dest) = renamed.remove(&r.src) {
+            r.dest = dest;
+        }
+        scan.repos.push(r);
+    }
+}
+
+/// `dest` の末尾に、まだ使われていない番号を付ける
+///
+/// ベアリポジトリの `.git` は残し、`foo.git` なら `foo-2.git` にする。
+fn with_suffix(dest: &Path, taken: &HashSet<PathBuf>) -> PathBuf {
+    let (stem, extension) = match dest.to_str().and_then(|d| d.strip_suffix(".git")) {
+        Some(stem) => (OsString::from(stem), ".git"),
+        None => (dest.as_os_str().to_os_string(), ""),
+    };
+    (2..)
+        .map(|n| {
+            let mut name = stem.clone();
+            name.push(format!("-{}{}", n, extension));
+            PathBuf::from(name)
+        })
+        .find(|p| !taken.contains(p) && fs::symlink_metadata(p).is_err())
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, path::PathBuf};
+
+    use super::{ConflictStrategy, find_conflicts, resolve_conflicts, with_suffix};
+    use crate::{
+        safety::Safety,
+        scan::{Repo, RepoKind, Scan},
+    };
+
+    fn repo(src: &str, dest: &str) -> Repo {
+        Repo {
+            src: PathBuf::from(src),
+            dest: PathBuf::from(dest),
+            relative: PathBuf::from("github.com/o/dup"),
+            kind: RepoKind::Normal,
+            remote: "origin".to_string(),
+            url: "https://github.com/o/dup".to_string(),
+            nested_in: None,
+            worktrees: Vec::new(),
+            safety: Safety::default(),
+        }
+    }
+
+    #[test]
+    fn breaks_ties_by_path() {
+        let cases = [
+            (
+                ConflictStrategy::Newest,
+                vec![("/nonexistent/src/dup1", "dup")],
+            ),
+            (
+                ConflictStrategy::MostRefs,
+                vec![("/nonexistent/src/dup1", "dup")],
+            ),
+            (
+                ConflictStrategy::Suffix,
+                vec![
+                    ("/nonexistent/src/old/dup2", "dup-2"),
+                    ("/nonexistent/src/dup1", "dup"),
+                ],
+            ),
+        ];
+
+        for (strategy, expected) in cases {
+            // `--nested both` では深いものから順に並ぶ
+            let mut scan = Scan {
+                repos: vec![
+                    repo("/nonexistent/src/old/dup2", "/nonexistent/ghq/dup"),
+                    repo("/nonexistent/src/dup1", "/nonexistent/ghq/dup"),
+                ],
+                skipped: Vec::new(),
+            };
+            let conflicts = find_conflicts(&scan.repos);
+            resolve_conflicts(&mut scan, &conflicts, strategy);
+
+            let moves: Vec<_> = scan
+                .repos
+                .iter()
+                .map(|r| (r.src.clone(), r.dest.clone()))
+                .collect();
+            let expected: Vec<_> = expected
+                .into_iter()
+                .map(|(src, dest)| {
+                    (
+                        PathBuf::from(src),
+                        PathBuf::from("/nonexistent/ghq").join(dest),
+                    )
+                })
+                .collect();
+            assert_eq!(moves, expected, "{:?}", strategy);
+        }
+    }
+
+    #[test]
+    fn numbers_conflicting_destinations() {
+        let taken: HashSet<PathBuf> = ["/nonexistent/ghq/github.com/o/taken-2"]
+            .into_iter()
+            .map(PathBuf::from)
+            .collect();
+        let cases = [
+            (
+                "/nonexistent/ghq/github.com/o/foo",
+                "/nonexistent/ghq/github.com/o/foo-2",
+            ),
+            (
+                "/nonexistent/ghq/github.com/o/foo.git",
+                "/nonexistent/ghq/github.com/o/foo-2.git",
+            ),
+            (
+                "/nonexistent/ghq/github.com/o/taken",
+                "/nonexistent/ghq/github.com/o/taken-3",
+            ),
+        ];
+
+        for (dest, expected) in cases {
+            assert_eq!(
+                with_suffix(&PathBuf::from(dest), &taken),
+                PathBuf::from(expected),
+                "{}",
+                dest
+            );
+        }
+    }
+}
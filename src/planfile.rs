@@ -0,0 +1,64 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{journal, scan::Repo};
+
+const HEADER: &str = "\
+# ghq-mover plan: one move per line.
+# Edit \"destination\" or delete lines, then run `ghq-mover apply <this file>`.
+# Lines starting with # are ignored.";
+
+/// 計画ファイルの 1 行 (1 つの移動)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub source: PathBuf,
+    pub destination: PathBuf,
+    /// 移動先を決めるのに使ったリモート
+    pub remote: String,
+    /// 計画を作ったときの HEAD (`apply` のときに変わっていれば実行しない)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub head: Option<String>,
+}
+
+/// 計画ファイルを書き出す
+///
+/// 手で行を消したり書き換えたりしやすいよう、1 行に 1 つの移動を JSON で書く。
+pub fn write(path: &Path, repos: &[&Repo]) -> Result<()> {
+    let file = File::create(path).with_context(|| format!("Failed to create {:?}", path))?;
+    let mut out = BufWriter::new(file);
+    writeln!(out, "{}", HEADER)?;
+    for repo in repos {
+        let entry = Entry {
+            source: repo.src.clone(),
+            destination: repo.dest.clone(),
+            remote: repo.remote.clone(),
+            head: journal::head(&repo.src),
+        };
+        writeln!(out, "{}", serde_json::to_string(&entry)?)?;
+    }
+    out.flush()
+        .with_context(|| format!("Failed to write {:?}", path))
+}
+
+/// 計画ファイルを読む
+pub fn read(path: &Path) -> Result<Vec<Entry>> {
+    let file = File::open(path).with_context(|| format!("Failed to open plan {:?}", path))?;
+    let mut entries = Vec::new();
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let entry = serde_json::from_str(line)
+            .with_context(|| format!("{}:{}: invalid plan entry", path.display(), i + 1))?;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
@@ -0,0 +1,222 @@
+use std::{
+    fmt,
+    io::{self, IsTerminal},
+    path::PathBuf,
+};
+
+use anyhow::{Result, bail};
+
+use crate::{cli::GlobalArgs, output};
+
+/// stdin が端末でないときに確認を省略するための環境変数
+const ASSUME_YES_ENV: &str = "GHQ_MOVER_ASSUME_YES";
+
+/// 入力の途中で EOF (Ctrl-D) になった
+#[derive(Debug)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "input ended; operation cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// 確認用プロンプト
+///
+/// `--yes` / `--no` が指定されていればそれに従い、stdin が端末でない場合は
+/// `GHQ_MOVER_ASSUME_YES` が設定されていない限りエラーにする。
+pub fn confirm(global: &GlobalArgs, prompt: &str) -> Result<bool> {
+    if global.yes {
+        return Ok(true);
+    }
+    if global.no {
+        return Ok(false);
+    }
+    if !io::stdin().is_terminal() {
+        if assume_yes_from_env() {
+            return Ok(true);
+        }
+        bail!(
+            "stdin is not a terminal; pass --yes or --no, or set {}=1",
+            ASSUME_YES_ENV
+        );
+    }
+
+    let Some(input) = read_line(prompt)? else {
+        return Ok(false);
+    };
+    Ok(matches!(input.to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// 選択肢から 1 つ選ばせる (対話できない場合は先頭を選ぶ)
+///
+/// EOF になった場合は [`Cancelled`] を返す。
+pub fn select(global: &GlobalArgs, prompt: &str, items: &[String]) -> Result<usize> {
+    if items.len() < 2 || global.yes || global.no || !io::stdin().is_terminal() {
+        return Ok(0);
+    }
+
+    say!("{}", prompt);
+    for (i, item) in items.iter().enumerate() {
+        say!("  [{}] {}", i + 1, item);
+    }
+    loop {
+        let Some(input) = read_line(&format!("Select [1-{}] (default 1): ", items.len()))? else {
+            return Err(Cancelled.into());
+        };
+        if input.is_empty() {
+            return Ok(0);
+        }
+        match input.parse::<usize>() {
+            Ok(n) if (1..=items.len()).contains(&n) => return Ok(n - 1),
+            _ => say!("⚠️  Please enter a number between 1 and {}.", items.len()),
+        }
+    }
+}
+
+/// リポジトリごとの選択
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Choice {
+    Move,
+    Skip,
+    /// 入力された移動先に移動する
+    MoveTo(PathBuf),
+    /// これ以降もすべて移動する
+    All,
+    /// これ以降はすべてスキップする
+    Quit,
+}
+
+/// 1 つのリポジトリをどうするか選ばせる (EOF になったら `None`)
+pub fn choose(prompt: &str) -> Result<Option<Choice>> {
+    require_terminal()?;
+    loop {
+        let Some(input) = read_line(&format!("{} [m,s,e,a,q,?]: ", prompt))? else {
+            return Ok(None);
+        };
+        match input.to_lowercase().as_str() {
+            "m" | "y" => return Ok(Some(Choice::Move)),
+            "s" | "n" => return Ok(Some(Choice::Skip)),
+            "e" => {
+                let Some(dest) = read_line("New destination (empty to go back): ")? else {
+                    return Ok(None);
+                };
+                if !dest.is_empty() {
+                    return Ok(Some(Choice::MoveTo(PathBuf::from(dest))));
+                }
+            }
+            "a" => return Ok(Some(Choice::All)),
+            "q" => return Ok(Some(Choice::Quit)),
+            _ => say!(
+                "m - move this repository\n\
+                 s - skip this repository\n\
+                 e - edit the destination, then move\n\
+                 a - move this and all remaining repositories\n\
+                 q - skip this and all remaining repositories"
+            ),
+        }
+    }
+}
+
+/// チェックボックス形式で選ばせる (`checked` が初期値で、選んだ結果に更新される)
+///
+/// EOF になった場合は選択を取りやめたものとして `false` を返す。
+pub fn checklist(items: &[String], checked: &mut [bool]) -> Result<bool> {
+    require_terminal()?;
+    loop {
+        for (i, item) in items.iter().enumerate() {
+            let mark = if checked[i] { "x" } else { " " };
+            say!("  [{}] {:>3}. {}", mark, i + 1, item);
+        }
+        let Some(input) =
+            read_line("Toggle numbers (e.g. 1 3-5), a = all, n = none, Enter = done: ")?
+        else {
+            return Ok(false);
+        };
+        match input.to_lowercase().as_str() {
+            "" => return Ok(true),
+            "a" => checked.fill(true),
+            "n" => checked.fill(false),
+            _ => match parse_selection(&input, items.len()) {
+                Some(indices) => indices.into_iter().for_each(|i| checked[i] = !checked[i]),
+                None => say!(
+                    "⚠️  Please enter numbers or ranges between 1 and {}.",
+                    items.len()
+                ),
+            },
+        }
+    }
+}
+
+/// `1 3-5,7` のような入力を 0 始まりの番号に変換する
+fn parse_selection(input: &str, len: usize) -> Option<Vec<usize>> {
+    let mut indices = Vec::new();
+    for part in input.split([' ', ',']).filter(|p| !p.is_empty()) {
+        let (start, end): (usize, usize) = match part.split_once('-') {
+            Some((start, end)) => (start.trim().parse().ok()?, end.trim().parse().ok()?),
+            None => {
+                let n = part.parse().ok()?;
+                (n, n)
+            }
+        };
+        if start < 1 || end > len || start > end {
+            return None;
+        }
+        indices.extend(start - 1..end);
+    }
+    Some(indices)
+}
+
+fn require_terminal() -> Result<()> {
+    if !io::stdin().is_terminal() {
+        bail!("stdin is not a terminal; interactive selection is not available");
+    }
+    Ok(())
+}
+
+/// 1 行読む (EOF なら `None`)
+fn read_line(prompt: &str) -> Result<Option<String>> {
+    output::human_prompt(prompt)?;
+
+    let mut input = String::new();
+    if io::stdin().read_line(&mut input)? == 0 {
+        // 次の出力がプロンプトと同じ行に続かないようにする
+        say!("");
+        return Ok(None);
+    }
+    Ok(Some(input.trim().to_string()))
+}
+
+fn assume_yes_from_env() -> bool {
+    std::env::var(ASSUME_YES_ENV)
+        .is_ok_and(|v| matches!(v.trim().to_lowercase().as_str(), "1" | "y" | "yes" | "true"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_selection;
+
+    #[test]
+    fn parses_selections() {
+        let cases: [(&str, Option<Vec<usize>>); 12] = [
+            ("1", Some(vec![0])),
+            ("1 3-5,7", Some(vec![0, 2, 3, 4, 6])),
+            ("  2 ,, 4  ", Some(vec![1, 3])),
+            ("3-3", Some(vec![2])),
+            ("1-7", Some(vec![0, 1, 2, 3, 4, 5, 6])),
+            ("", Some(vec![])),
+            ("0", None),
+            ("8", None),
+            ("5-3", None),
+            ("6-8", None),
+            ("x", None),
+            ("1-", None),
+        ];
+
+        for (input, expected) in cases {
+            assert_eq!(parse_selection(input, 7), expected, "{:?}", input);
+        }
+    }
+}
@@ -0,0 +1,402 @@
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use git2::{Config, Repository};
+
+use crate::mover;
+
+/// リンクされたワークツリー
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Worktree {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+/// 作業ツリーを持つリポジトリなら `.git`、ベアリポジトリならそれ自身が git ディレクトリ
+pub fn git_dir(repo_root: &Path) -> PathBuf {
+    let dot_git = repo_root.join(".git");
+    if dot_git.is_dir() {
+        dot_git
+    } else {
+        repo_root.to_path_buf()
+    }
+}
+
+/// `old` から `new` に移動したリポジトリと、そのワークツリーの相互参照を書き直す
+///
+/// `.git/worktrees/<name>/gitdir` はワークツリーの `.git` ファイルを、
+/// ワークツリーの `.git` ファイルは `.git/worktrees/<name>` を絶対パスで指しているので、
+/// 移動した側を指すように両方を更新する。libgit2 で作られたワークツリーは
+/// `commondir` も絶対パスなので、あわせて更新する。
+pub fn worktrees(old: &Path, new: &Path) -> Result<Vec<Worktree>> {
+    let admin_root = git_dir(new).join("worktrees");
+    let Ok(entries) = fs::read_dir(&admin_root) else {
+        return Ok(Vec::new());
+    };
+
+    let mut worktrees = Vec::new();
+    for entry in entries.flatten() {
+        let admin_dir = entry.path();
+        let gitdir_file = admin_dir.join("gitdir");
+        let Ok(content) = fs::read_to_string(&gitdir_file) else {
+            continue;
+        };
+        // 相対パス (worktree.useRelativePaths) は移動前の管理ディレクトリが基準
+        let old_admin_dir = rebase(&admin_dir, new, old).unwrap_or_else(|| admin_dir.clone());
+        let recorded = clean(&old_admin_dir.join(content.trim()));
+        let dot_git = rebase(&recorded, old, new).unwrap_or(recorded);
+
+        fs::write(&gitdir_file, format!("{}\n", dot_git.display()))
+            .with_context(|| format!("Failed to update {:?}", gitdir_file))?;
+        if dot_git.is_file() {
+            fs::write(&dot_git, format!("gitdir: {}\n", admin_dir.display()))
+                .with_context(|| format!("Failed to update {:?}", dot_git))?;
+        }
+        let commondir_file = admin_dir.join("commondir");
+        if let Ok(content) = fs::read_to_string(&commondir_file) {
+            let common = Path::new(content.trim());
+            if let Some(rebased) = common
+                .is_absolute()
+                .then(|| rebase(common, old, new))
+                .flatten()
+            {
+                fs::write(&commondir_file, format!("{}\n", rebased.display()))
+                    .with_context(|| format!("Failed to update {:?}", commondir_file))?;
+            }
+        }
+
+        worktrees.push(Worktree {
+            name: entry.file_name().to_string_lossy().into_owned(),
+            path: dot_git.parent().unwrap_or(&dot_git).to_path_buf(),
+        });
+    }
+    worktrees.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(worktrees)
+}
+
+/// ワークツリーを `to` に移動し、リポジトリ側の参照を更新する
+pub fn relocate_worktree(repo_root: &Path, worktree: &Worktree, to: &Path) -> Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    mover::move_dir(&worktree.path, to)?;
+
+    let gitdir_file = git_dir(repo_root)
+        .join("worktrees")
+        .join(&worktree.name)
+        .join("gitdir");
+    fs::write(&gitdir_file, format!("{}\n", to.join(".git").display()))
+        .with_context(|| format!("Failed to update {:?}", gitdir_file))
+}
+
+/// `old` から `new` に移動したリポジトリのサブモジュール (入れ子も含む) の絶対パスを相対パスに直す
+///
+/// サブモジュールの `.git` ファイルと `.git/modules/<name>/config` の `core.worktree`、
+/// および `--separate-git-dir` のリポジトリ自身の参照を対象にする。
+/// 直した後も開けなかったサブモジュールのパスを返す。
+pub fn submodules(old: &Path, new: &Path) -> Result<Vec<PathBuf>> {
+    fix_gitfile(&new.join(".git"), old, new)?;
+    if let Ok(repo) = Repository::open(new) {
+        fix_core_worktree(repo.path(), old, new)?;
+    }
+
+    let mut broken = Vec::new();
+    fix_submodules_in(new, old, new, &mut broken)?;
+    Ok(broken)
+}
+
+fn fix_submodules_in(
+    workdir: &Path,
+    old: &Path,
+    new: &Path,
+    broken: &mut Vec<PathBuf>,
+) -> Result<()> {
+    let Ok(repo) = Repository::open(workdir) else {
+        return Ok(());
+    };
+    let Ok(submodules) = repo.submodules() else {
+        return Ok(());
+    };
+
+    for submodule in submodules {
+        let sub_dir = workdir.join(submodule.path());
+        let dot_git = sub_dir.join(".git");
+        if !dot_git.exists() {
+            // 未初期化
+            continue;
+        }
+        fix_gitfile(&dot_git, old, new)?;
+        fix_core_worktree(
+            &repo
+                .path()
+                .join("modules")
+                .join(submodule.name().unwrap_or("")),
+            old,
+            new,
+        )?;
+
+        match Repository::open(&sub_dir) {
+            Ok(sub) => {
+                fix_core_worktree(sub.path(), old, new)?;
+                fix_submodules_in(&sub_dir, old, new, broken)?;
+            }
+            Err(_) => broken.push(sub_dir),
+        }
+    }
+    Ok(())
+}
+
+/// `gitdir: <path>` が移動前の絶対パスなら、移動後の相対パスに書き換える
+fn fix_gitfile(dot_git: &Path, old: &Path, new: &Path) -> Result<()> {
+    if !dot_git.is_file() {
+        return Ok(());
+    }
+    let content = fs::read_to_string(dot_git)?;
+    let Some(target) = content.trim().strip_prefix("gitdir:") else {
+        return Ok(());
+    };
+    let target = Path::new(target.trim());
+    let Some(rebased) = target
+        .is_absolute()
+        .then(|| rebase(target, old, new))
+        .flatten()
+    else {
+        return Ok(());
+    };
+
+    let base = dot_git.parent().unwrap_or(dot_git);
+    let relative = relative(&rebased, base);
+    debug!("🔗 {:?}: gitdir {:?} → {:?}", dot_git, target, relative);
+    fs::write(dot_git, format!("gitdir: {}\n", relative.display()))
+        .with_context(|| format!("Failed to update {:?}", dot_git))
+}
+
+/// `core.worktree` が移動前の絶対パスなら、git ディレクトリからの相対パスに書き換える
+fn fix_core_worktree(git_dir: &Path, old: &Path, new: &Path) -> Result<()> {
+    let config_path = git_dir.join("config");
+    if !config_path.is_file() {
+        return Ok(());
+    }
+    let mut config = Config::open(&config_path)?;
+    let Ok(worktree) = config.get_path("core.worktree") else {
+        return Ok(());
+    };
+    let Some(rebased) = worktree
+        .is_absolute()
+        .then(|| rebase(&worktree, old, new))
+        .flatten()
+    else {
+        return Ok(());
+    };
+
+    let relative = relative(&rebased, git_dir);
+    debug!(
+        "🔗 {:?}: core.worktree {:?} → {:?}",
+        config_path, worktree, relative
+    );
+    config
+        .set_str("core.worktree", &relative.to_string_lossy())
+        .with_context(|| format!("Failed to update {:?}", config_path))
+}
+
+/// `base` から `path` への相対パス (どちらも絶対パス)
+pub fn relative(path: &Path, base: &Path) -> PathBuf {
+    let path = clean(path);
+    let base = clean(base);
+    let common = path
+        .components()
+        .zip(base.components())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let mut relative = PathBuf::new();
+    for _ in base.components().skip(common) {
+        relative.push("..");
+    }
+    relative.extend(path.components().skip(common));
+    if relative.as_os_str().is_empty() {
+        relative.push(".");
+    }
+    relative
+}
+
+/// `.` と `..` を取り除く (ファイルシステムは参照しない)
+pub fn clean(path: &Path) -> PathBuf {
+    let mut cleaned = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                cleaned.pop();
+            }
+            c => cleaned.push(c),
+        }
+    }
+    cleaned
+}
+
+/// `path` が `old` 以下にあれば、`new` 以下の同じ位置に置き換える
+pub fn rebase(path: &Path, old: &Path, new: &Path) -> Option<PathBuf> {
+    path.strip_prefix(old).ok().map(|rest| new.join(rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use git2::{Repository, Signature};
+
+    use super::{Worktree, clean, fix_gitfile, rebase, relative, relocate_worktree, worktrees};
+
+    #[test]
+    fn computes_relative_paths() {
+        let cases = [
+            ("/a/b/c", "/a/b", "c"),
+            ("/a/b", "/a/b/c", ".."),
+            ("/a/x/y", "/a/b/c", "../../x/y"),
+            ("/a/b", "/a/b", "."),
+            ("/a/b/../c", "/a/./b", "../c"),
+            ("/x", "/a/b", "../../x"),
+        ];
+
+        for (path, base, expected) in cases {
+            assert_eq!(
+                relative(path.as_ref(), base.as_ref()),
+                PathBuf::from(expected),
+                "{} from {}",
+                path,
+                base
+            );
+        }
+    }
+
+    #[test]
+    fn cleans_paths() {
+        let cases = [
+            ("/a/./b", "/a/b"),
+            ("/a/b/../c", "/a/c"),
+            ("/a/b/c/../../d", "/a/d"),
+            ("/a/b/", "/a/b"),
+        ];
+
+        for (path, expected) in cases {
+            assert_eq!(clean(path.as_ref()), PathBuf::from(expected), "{}", path);
+        }
+    }
+
+    #[test]
+    fn rebases_paths() {
+        let cases = [
+            (
+                "/src/repo/.git/worktrees/wt",
+                Some("/ghq/repo/.git/worktrees/wt"),
+            ),
+            ("/src/repo", Some("/ghq/repo")),
+            ("/src/repo-other/.git", None),
+            ("/elsewhere/wt", None),
+        ];
+
+        for (path, expected) in cases {
+            assert_eq!(
+                rebase(path.as_ref(), "/src/repo".as_ref(), "/ghq/repo".as_ref()),
+                expected.map(PathBuf::from),
+                "{}",
+                path
+            );
+        }
+    }
+
+    /// コミットが 1 つあるリポジトリと、そのリンクされたワークツリーを作る
+    fn repo_with_worktree(repo_dir: &std::path::Path, worktree_dir: &std::path::Path) {
+        let repo = Repository::init(repo_dir).unwrap();
+        let signature = Signature::now("test", "test@example.com").unwrap();
+        let tree = repo
+            .find_tree(repo.index().unwrap().write_tree().unwrap())
+            .unwrap();
+        repo.commit(Some("HEAD"), &signature, &signature, "init", &tree, &[])
+            .unwrap();
+        repo.worktree("wt", worktree_dir, None).unwrap();
+    }
+
+    #[test]
+    fn repairs_worktrees_after_moving_the_repository() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        let (old, new, wt) = (root.join("old"), root.join("new"), root.join("wt"));
+        repo_with_worktree(&old, &wt);
+
+        fs::rename(&old, &new).unwrap();
+        let found = worktrees(&old, &new).unwrap();
+
+        assert_eq!(
+            found,
+            [Worktree {
+                name: "wt".to_string(),
+                path: wt.clone(),
+            }]
+        );
+        let admin_dir = new.join(".git/worktrees/wt");
+        assert_eq!(
+            fs::read_to_string(admin_dir.join("gitdir")).unwrap().trim(),
+            wt.join(".git").to_str().unwrap()
+        );
+        assert_eq!(
+            fs::read_to_string(wt.join(".git")).unwrap().trim(),
+            format!("gitdir: {}", admin_dir.display())
+        );
+        let opened = Repository::open(&wt).unwrap();
+        assert!(opened.is_worktree());
+        assert_eq!(opened.commondir().canonicalize().unwrap(), new.join(".git"));
+    }
+
+    #[test]
+    fn relocates_worktrees() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        let (repo, wt, to) = (
+            root.join("repo"),
+            root.join("wt"),
+            root.join("repo.worktrees/wt"),
+        );
+        repo_with_worktree(&repo, &wt);
+
+        let worktree = Worktree {
+            name: "wt".to_string(),
+            path: wt.clone(),
+        };
+        relocate_worktree(&repo, &worktree, &to).unwrap();
+
+        assert!(!wt.exists());
+        assert_eq!(
+            fs::read_to_string(repo.join(".git/worktrees/wt/gitdir"))
+                .unwrap()
+                .trim(),
+            to.join(".git").to_str().unwrap()
+        );
+        assert!(Repository::open(&to).unwrap().is_worktree());
+    }
+
+    #[test]
+    fn makes_absolute_gitfiles_relative() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        let (old, new) = (root.join("old"), root.join("new"));
+        fs::create_dir_all(new.join("lib/sub")).unwrap();
+        let dot_git = new.join("lib/sub/.git");
+        fs::write(
+            &dot_git,
+            format!("gitdir: {}\n", old.join(".git/modules/sub").display()),
+        )
+        .unwrap();
+
+        fix_gitfile(&dot_git, &old, &new).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(&dot_git).unwrap(),
+            "gitdir: ../../.git/modules/sub\n"
+        );
+    }
+}
@@ -0,0 +1,193 @@
+use std::{io::Write, path::Path};
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::{
+    output::Format,
+    safety::Safety,
+    scan::{Repo, RepoKind, Skipped},
+};
+
+/// JSON の形式を変えたら上げる
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// リポジトリ 1 つ分の結果
+#[derive(Debug, Serialize)]
+pub struct RepoRecord {
+    pub source: String,
+    pub destination: String,
+    pub kind: RepoKind,
+    pub remote: String,
+    pub url: String,
+    pub host: String,
+    /// `owner` やグループ (GitLab のサブグループなどは `/` 区切り)
+    pub owner: String,
+    pub name: String,
+    /// `scan` は `found`、`plan` は判定結果、`move` は `moved` / `failed`
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nested_in: Option<String>,
+    pub worktrees: Vec<WorktreeRecord>,
+    pub unsaved: Safety,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WorktreeRecord {
+    pub name: String,
+    pub path: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SkippedRecord {
+    pub source: String,
+    pub status: &'static str,
+    pub reason: &'static str,
+    pub message: String,
+}
+
+impl RepoRecord {
+    pub fn new(repo: &Repo, status: &str, message: Option<String>) -> Self {
+        let mut segments: Vec<String> = repo
+            .relative
+            .iter()
+            .map(|s| s.to_string_lossy().into_owned())
+            .collect();
+        let name = segments.pop().unwrap_or_default();
+        let host = if segments.is_empty() {
+            String::new()
+        } else {
+            segments.remove(0)
+        };
+        RepoRecord {
+            source: path_string(&repo.src),
+            destination: path_string(&repo.dest),
+            kind: repo.kind,
+            remote: repo.remote.clone(),
+            url: repo.url.clone(),
+            host,
+            owner: segments.join("/"),
+            name,
+            status: status.to_string(),
+            message,
+            nested_in: repo.nested_in.as_deref().map(path_string),
+            worktrees: repo
+                .worktrees
+                .iter()
+                .map(|w| WorktreeRecord {
+                    name: w.name.clone(),
+                    path: path_string(&w.path),
+                })
+                .collect(),
+            unsaved: repo.safety.clone(),
+        }
+    }
+}
+
+impl From<&Skipped> for SkippedRecord {
+    fn from(skipped: &Skipped) -> Self {
+        SkippedRecord {
+            source: path_string(&skipped.path),
+            status: "skipped",
+            reason: skipped.reason.code(),
+            message: skipped.reason.to_string(),
+        }
+    }
+}
+
+/// `--format json` / `ndjson` の出力
+///
+/// `ndjson` は最初に `version` を持つ `header` 行を、その後は追加されるたびに
+/// `type` 付きで 1 行ずつ出力し、`json` は `finish` でまとめて 1 つのオブジェクトとして出力する。
+pub struct Report {
+    format: Format,
+    repositories: Vec<RepoRecord>,
+    skipped: Vec<SkippedRecord>,
+}
+
+#[derive(Serialize)]
+struct Line<'a, T> {
+    #[serde(rename = "type")]
+    kind: &'a str,
+    #[serde(flatten)]
+    record: &'a T,
+}
+
+#[derive(Serialize)]
+struct Header {
+    version: u32,
+}
+
+#[derive(Serialize)]
+struct Document<'a> {
+    version: u32,
+    repositories: &'a [RepoRecord],
+    skipped: &'a [SkippedRecord],
+}
+
+impl Report {
+    pub fn new(format: Format) -> Result<Self> {
+        if format == Format::Ndjson {
+            print_line(
+                "header",
+                &Header {
+                    version: SCHEMA_VERSION,
+                },
+            )?;
+        }
+        Ok(Report {
+            format,
+            repositories: Vec::new(),
+            skipped: Vec::new(),
+        })
+    }
+
+    pub fn repository(&mut self, record: RepoRecord) -> Result<()> {
+        if self.format == Format::Ndjson {
+            return print_line("repository", &record);
+        }
+        self.repositories.push(record);
+        Ok(())
+    }
+
+    pub fn skipped(&mut self, skipped: &[Skipped]) -> Result<()> {
+        for s in skipped {
+            let record = SkippedRecord::from(s);
+            if self.format == Format::Ndjson {
+                print_line("skipped", &record)?;
+            } else {
+                self.skipped.push(record);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<()> {
+        if self.format != Format::Json {
+            return Ok(());
+        }
+        let document = Document {
+            version: SCHEMA_VERSION,
+            repositories: &self.repositories,
+            skipped: &self.skipped,
+        };
+        let mut stdout = std::io::stdout().lock();
+        serde_json::to_writer_pretty(&mut stdout, &document)?;
+        writeln!(stdout)?;
+        Ok(())
+    }
+}
+
+fn print_line<T: Serialize>(kind: &str, record: &T) -> Result<()> {
+    let mut stdout = std::io::stdout().lock();
+    serde_json::to_writer(&mut stdout, &Line { kind, record })?;
+    writeln!(stdout)?;
+    stdout.flush()?;
+    Ok(())
+}
+
+fn path_string(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
@@ -0,0 +1,174 @@
+use git2::Config;
+
+/// git の `url.<base>.insteadOf` / `url.<base>.pushInsteadOf` による URL の書き換え
+#[derive(Debug, Clone, Default)]
+pub struct Rewrites {
+    /// (置き換え前の前方一致, 置き換え後のベース)
+    instead_of: Vec<(String, String)>,
+    push_instead_of: Vec<(String, String)>,
+}
+
+impl Rewrites {
+    pub fn from_config(config: &Config) -> Self {
+        let mut rewrites = Rewrites::default();
+        let Ok(entries) = config.entries(Some(r"^url\..*\.(push)?insteadof$")) else {
+            return rewrites;
+        };
+        let _ = entries.for_each(|entry| {
+            let (Some(name), Some(prefix)) = (entry.name(), entry.value()) else {
+                return;
+            };
+            let Some(name) = name.strip_prefix("url.") else {
+                return;
+            };
+            if let Some(base) = name.strip_suffix(".insteadof") {
+                rewrites
+                    .instead_of
+                    .push((prefix.to_string(), base.to_string()));
+            } else if let Some(base) = name.strip_suffix(".pushinsteadof") {
+                rewrites
+                    .push_instead_of
+                    .push((prefix.to_string(), base.to_string()));
+            }
+        });
+        rewrites
+    }
+
+    /// fetch に使われる URL
+    pub fn fetch_url(&self, url: &str) -> String {
+        rewrite(&self.instead_of, url).unwrap_or_else(|| url.to_string())
+    }
+
+    /// push に使われる URL
+    ///
+    /// git と同様に、`pushurl` があればそれに `insteadOf` だけを適用し、
+    /// なければ `url` に `pushInsteadOf` を (一致しなければ `insteadOf` を) 適用する。
+    pub fn push_url(&self, url: &str, pushurl: Option<&str>) -> String {
+        match pushurl {
+            Some(pushurl) => self.fetch_url(pushurl),
+            None => rewrite(&self.push_instead_of, url).unwrap_or_else(|| self.fetch_url(url)),
+        }
+    }
+}
+
+/// 最も長く一致した前方一致を置き換える
+fn rewrite(rules: &[(String, String)], url: &str) -> Option<String> {
+    rules
+        .iter()
+        .filter(|(prefix, _)| url.starts_with(prefix.as_str()))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|(prefix, base)| format!("{}{}", base, &url[prefix.len()..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use git2::Config;
+
+    use super::Rewrites;
+
+    fn rules(rules: &[(&str, &str)]) -> Vec<(String, String)> {
+        rules
+            .iter()
+            .map(|(prefix, base)| (prefix.to_string(), base.to_string()))
+            .collect()
+    }
+
+    fn rewrites() -> Rewrites {
+        Rewrites {
+            instead_of: rules(&[
+                ("gh:", "https://github.com/"),
+                ("https://github.com/", "https://mirror.example.com/github/"),
+                ("https://github.com/corp/", "https://git.corp.example.com/"),
+            ]),
+            push_instead_of: rules(&[("https://mirror.example.com/", "git@github.com:")]),
+        }
+    }
+
+    #[test]
+    fn rewrites_fetch_urls() {
+        let cases = [
+            ("gh:owner/repo", "https://github.com/owner/repo"),
+            // 最も長く一致したものが使われる
+            (
+                "https://github.com/owner/repo",
+                "https://mirror.example.com/github/owner/repo",
+            ),
+            (
+                "https://github.com/corp/repo",
+                "https://git.corp.example.com/repo",
+            ),
+            (
+                "https://gitlab.com/owner/repo",
+                "https://gitlab.com/owner/repo",
+            ),
+        ];
+
+        for (url, expected) in cases {
+            assert_eq!(rewrites().fetch_url(url), expected, "{}", url);
+        }
+    }
+
+    #[test]
+    fn rewrites_push_urls() {
+        let cases = [
+            // pushInsteadOf が優先される
+            (
+                "https://mirror.example.com/owner/repo",
+                None,
+                "git@github.com:owner/repo",
+            ),
+            // pushInsteadOf に一致しなければ insteadOf
+            ("gh:owner/repo", None, "https://github.com/owner/repo"),
+            (
+                "https://gitlab.com/owner/repo",
+                None,
+                "https://gitlab.com/owner/repo",
+            ),
+            // pushurl には insteadOf だけが適用される
+            (
+                "https://gitlab.com/owner/repo",
+                Some("gh:owner/repo"),
+                "https://github.com/owner/repo",
+            ),
+            (
+                "https://gitlab.com/owner/repo",
+                Some("https://mirror.example.com/owner/repo"),
+                "https://mirror.example.com/owner/repo",
+            ),
+        ];
+
+        for (url, pushurl, expected) in cases {
+            assert_eq!(
+                rewrites().push_url(url, pushurl),
+                expected,
+                "{} {:?}",
+                url,
+                pushurl
+            );
+        }
+    }
+
+    #[test]
+    fn reads_rules_from_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config");
+        fs::write(
+            &path,
+            "[url \"https://github.com/\"]\n\tinsteadOf = gh:\n\tinsteadOf = github:\n\
+             [url \"git@github.com:\"]\n\tpushInsteadOf = https://github.com/\n",
+        )
+        .unwrap();
+        let rewrites = Rewrites::from_config(&Config::open(&path).unwrap());
+
+        assert_eq!(
+            rewrites.fetch_url("github:owner/repo"),
+            "https://github.com/owner/repo"
+        );
+        assert_eq!(
+            rewrites.push_url("https://github.com/owner/repo", None),
+            "git@github.com:owner/repo"
+        );
+    }
+}
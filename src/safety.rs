@@ -0,0 +1,117 @@
+use std::fmt;
+
+use git2::{BranchType, Repository, StatusOptions};
+use serde::Serialize;
+
+/// 移動前に確認しておきたい、まだどこにも保存されていない作業
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Safety {
+    /// コミットされていない変更 (ステージ済みを含む) のあるファイル数
+    pub uncommitted: usize,
+    pub untracked: usize,
+    pub stashes: usize,
+    /// upstream より進んでいるローカルブランチ
+    pub ahead: Vec<Ahead>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Ahead {
+    pub branch: String,
+    pub upstream: String,
+    /// upstream にないコミットの数
+    pub commits: usize,
+}
+
+impl Safety {
+    /// 作業ツリー、スタッシュ、ローカルブランチを調べる
+    ///
+    /// 調べられなかった項目は問題なしとして扱う。
+    pub fn check(repo: &mut Repository) -> Self {
+        let mut safety = Safety::default();
+
+        if !repo.is_bare() {
+            let mut opts = StatusOptions::new();
+            // サブモジュールは親リポジトリと一緒に移動するので、中身の変更は問題にならない
+            opts.include_untracked(true)
+                .include_ignored(false)
+                .exclude_submodules(true);
+            if let Ok(statuses) = repo.statuses(Some(&mut opts)) {
+                for entry in statuses.iter() {
+                    if entry.status().is_wt_new() {
+                        safety.untracked += 1;
+                    } else {
+                        safety.uncommitted += 1;
+                    }
+                }
+            }
+        }
+
+        let _ = repo.stash_foreach(|_, _, _| {
+            safety.stashes += 1;
+            true
+        });
+
+        if let Ok(branches) = repo.branches(Some(BranchType::Local)) {
+            for (branch, _) in branches.flatten() {
+                let Ok(upstream) = branch.upstream() else {
+                    continue;
+                };
+                let (Some(local), Some(remote)) = (branch.get().target(), upstream.get().target())
+                else {
+                    continue;
+                };
+                let Ok((ahead, _)) = repo.graph_ahead_behind(local, remote) else {
+                    continue;
+                };
+                if ahead > 0 {
+                    safety.ahead.push(Ahead {
+                        branch: branch.name().ok().flatten().unwrap_or("?").to_string(),
+                        upstream: upstream.name().ok().flatten().unwrap_or("?").to_string(),
+                        commits: ahead,
+                    });
+                }
+            }
+        }
+        safety
+    }
+
+    pub fn is_clean(&self) -> bool {
+        *self == Safety::default()
+    }
+}
+
+impl fmt::Display for Safety {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if self.uncommitted > 0 {
+            parts.push(count(self.uncommitted, "uncommitted change"));
+        }
+        if self.untracked > 0 {
+            parts.push(count(self.untracked, "untracked file"));
+        }
+        if self.stashes > 0 {
+            parts.push(count(self.stashes, "stash"));
+        }
+        for ahead in &self.ahead {
+            parts.push(format!(
+                "{} ahead of {} by {}",
+                ahead.branch,
+                ahead.upstream,
+                count(ahead.commits, "commit")
+            ));
+        }
+        if parts.is_empty() {
+            write!(f, "clean")
+        } else {
+            write!(f, "{}", parts.join(", "))
+        }
+    }
+}
+
+fn count(n: usize, noun: &str) -> String {
+    match (n, noun.ends_with('h')) {
+        (1, _) => format!("1 {}", noun),
+        (_, true) => format!("{} {}es", n, noun),
+        (_, false) => format!("{} {}s", n, noun),
+    }
+}
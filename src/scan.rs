@@ -0,0 +1,670 @@
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use git2::Repository;
+use rayon::prelude::*;
+use serde::Serialize;
+
+use crate::{
+    filter::Filter,
+    ghq, layout,
+    repair::{self, Worktree},
+    rewrite::Rewrites,
+    safety::Safety,
+    ssh_config::SshConfig,
+};
+
+/// リポジトリの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RepoKind {
+    /// 作業ツリーを持つ通常のリポジトリ
+    Normal,
+    Bare,
+    /// `git clone --mirror` で作られたベアリポジトリ
+    Mirror,
+}
+
+impl fmt::Display for RepoKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RepoKind::Normal => write!(f, "normal"),
+            RepoKind::Bare => write!(f, "bare"),
+            RepoKind::Mirror => write!(f, "mirror"),
+        }
+    }
+}
+
+/// 移動対象のリポジトリ
+#[derive(Debug, Clone)]
+pub struct Repo {
+    pub src: PathBuf,
+    pub dest: PathBuf,
+    /// リモートから求めた ghq ルートからの相対パス (`<host>/<owner...>/<name>`)
+    pub relative: PathBuf,
+    pub kind: RepoKind,
+    /// 移動先の決定に使ったリモート名
+    pub remote: String,
+    /// `insteadOf` などを適用した後の実際の URL
+    pub url: String,
+    /// 別のリポジトリの作業ツリー内にある場合、その外側のリポジトリ
+    pub nested_in: Option<PathBuf>,
+    /// `git worktree add` で作られたワークツリー
+    pub worktrees: Vec<Worktree>,
+    /// コミットやプッシュがされていない作業
+    pub safety: Safety,
+}
+
+/// 移動しないリポジトリとその理由
+#[derive(Debug, Clone)]
+pub struct Skipped {
+    pub path: PathBuf,
+    pub reason: SkipReason,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SkipReason {
+    /// ディレクトリを走査できなかった
+    WalkError(String),
+    OpenFailed(String),
+    NoRemote,
+    /// 優先リストに一致するリモートがなく、リモートが複数ある
+    AmbiguousRemotes(Vec<String>),
+    NoUrl(String),
+    InvalidUrl(String),
+    /// URL から ghq のパスを組み立てられない
+    Layout(String, layout::LayoutError),
+    /// 外側のリポジトリと一緒に移動する
+    NestedIn(PathBuf),
+    /// 入れ子のリポジトリだけを取り出すため、そのまま残す
+    ContainsNested,
+    /// 走査範囲外にあるリポジトリのワークツリー
+    LinkedWorktree(PathBuf),
+    /// 移動しないことになったリポジトリのワークツリー
+    MainSkipped(PathBuf),
+    /// `--only-clean` で未保存の作業があるものを除いた
+    NotClean(Safety),
+    /// 同じ移動先になる別のリポジトリ (引数) を移動する
+    Superseded(PathBuf),
+    /// `--on-conflict skip` で、同じ移動先 (引数) になるものをすべて残した
+    Conflict(PathBuf),
+    /// 計画ファイルを作ったあとに状況が変わった
+    Stale(String),
+}
+
+impl SkipReason {
+    /// 機械向けの出力で使う識別子
+    pub fn code(&self) -> &'static str {
+        match self {
+            SkipReason::WalkError(_) => "walk_error",
+            SkipReason::OpenFailed(_) => "open_failed",
+            SkipReason::NoRemote => "no_remote",
+            SkipReason::AmbiguousRemotes(_) => "ambiguous_remotes",
+            SkipReason::NoUrl(_) => "no_url",
+            SkipReason::InvalidUrl(_) => "invalid_url",
+            SkipReason::Layout(..) => "layout",
+            SkipReason::NestedIn(_) => "nested_in",
+            SkipReason::ContainsNested => "contains_nested",
+            SkipReason::LinkedWorktree(_) => "linked_worktree",
+            SkipReason::MainSkipped(_) => "main_skipped",
+            SkipReason::NotClean(_) => "not_clean",
+            SkipReason::Superseded(_) => "superseded",
+            SkipReason::Conflict(_) => "conflict",
+            SkipReason::Stale(_) => "stale",
+        }
+    }
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SkipReason::WalkError(e) => write!(f, "could not be scanned: {}", e),
+            SkipReason::OpenFailed(e) => write!(f, "could not be opened as a repository: {}", e),
+            SkipReason::NoRemote => write!(f, "no remotes are configured"),
+            SkipReason::AmbiguousRemotes(names) => write!(
+                f,
+                "none of the preferred remotes exist (found: {}); use --remote to pick one",
+                names.join(", ")
+            ),
+            SkipReason::NoUrl(remote) => write!(f, "remote {:?} has no URL", remote),
+            SkipReason::InvalidUrl(url) => write!(f, "remote URL {:?} could not be parsed", url),
+            SkipReason::Layout(url, e) => write!(f, "remote URL {:?} {}", url, e),
+            SkipReason::NestedIn(outer) => {
+                write!(f, "nested inside {:?} and moves together with it", outer)
+            }
+            SkipReason::MainSkipped(main) => write!(
+                f,
+                "linked worktree of {:?}; left in place because its main repository is skipped",
+                main
+            ),
+            SkipReason::LinkedWorktree(main) => write!(
+                f,
+                "linked worktree of {:?}, which is outside the scanned directory",
+                main
+            ),
+            SkipReason::ContainsNested => write!(
+                f,
+                "contains nested repositories; left in place because of --nested inner"
+            ),
+            SkipReason::NotClean(safety) => {
+                write!(
+                    f,
+                    "has unsaved work ({}); left in place because of --only-clean",
+                    safety
+                )
+            }
+            SkipReason::Superseded(kept) => write!(
+                f,
+                "has the same destination as {:?}, which is moved instead",
+                kept
+            ),
+            SkipReason::Conflict(dest) => write!(
+                f,
+                "another repository also maps to {:?}; left in place because of --on-conflict skip",
+                dest
+            ),
+            SkipReason::Stale(why) => write!(f, "no longer matches the plan: {}", why),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Scan {
+    pub repos: Vec<Repo>,
+    pub skipped: Vec<Skipped>,
+}
+
+/// 走査と移動先の決定に使う設定
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub dests: ghq::Destinations,
+    /// 移動先の決定に使うリモート名 (優先順)
+    pub remotes: Vec<String>,
+    /// SSH のホストエイリアスを解決するための設定 (`None` なら解決しない)
+    pub ssh_config: Option<SshConfig>,
+    /// 走査しないディレクトリ
+    pub filter: Filter,
+    /// リポジトリを探す深さ (走査するディレクトリ自身が 0)
+    pub max_depth: Option<usize>,
+    /// 別のファイルシステムのディレクトリには入らない
+    pub one_file_system: bool,
+    /// ディレクトリへのシンボリックリンクをたどる
+    pub follow_links: bool,
+}
+
+/// `base` 以下のリポジトリを探し、移動先を決める
+///
+/// ディレクトリの走査とリポジトリの読み込みはどちらも並列に行い、結果はパス順に並べる。
+pub fn find_git_repos(base: &Path, opts: &Options) -> Result<Scan> {
+    let walker = Walker {
+        base,
+        opts,
+        device: if opts.one_file_system {
+            device(&fs::metadata(base)?)
+        } else {
+            None
+        },
+    };
+    let root = Ancestor {
+        id: fs::metadata(base).ok().and_then(|m| file_id(&m)),
+        real: base,
+        parent: None,
+    };
+    let found = walker.walk(base, &root, 0);
+
+    let mut roots = Vec::new();
+    let mut linked = Vec::new();
+    let mut scan = Scan::default();
+    for f in found {
+        match f {
+            Found::Repo(root) => roots.push(root),
+            Found::Linked(path, main) => linked.push((path, main)),
+            Found::Error(path, e) => scan.skipped.push(Skipped {
+                path,
+                reason: SkipReason::WalkError(e),
+            }),
+        }
+    }
+    // リンクをたどると同じリポジトリに別の経路で着くことがある
+    roots.sort();
+    roots.dedup();
+
+    let inspected: Vec<_> = roots
+        .into_par_iter()
+        .map(|root| {
+            let result = inspect(&root, opts);
+            (root, result)
+        })
+        .collect();
+    for (root, result) in inspected {
+        match result {
+            Ok(repo) => scan.repos.push(repo),
+            Err(reason) => scan.skipped.push(Skipped { path: root, reason }),
+        }
+    }
+
+    for (path, main) in linked {
+        if scan.repos.iter().any(|r| r.src == main) {
+            continue;
+        }
+        let reason = if scan.skipped.iter().any(|s| s.path == main) {
+            SkipReason::MainSkipped(main)
+        } else {
+            SkipReason::LinkedWorktree(main)
+        };
+        scan.skipped.push(Skipped { path, reason });
+    }
+    scan.skipped.sort_by(|a, b| a.path.cmp(&b.path));
+
+    Ok(scan)
+}
+
+/// 走査で見つかったもの
+enum Found {
+    /// リポジトリのルート
+    Repo(PathBuf),
+    /// リンクされたワークツリーとそのメインのリポジトリ
+    Linked(PathBuf, PathBuf),
+    Error(PathBuf, String),
+}
+
+struct Walker<'a> {
+    base: &'a Path,
+    opts: &'a Options,
+    /// `--one-file-system` のときの走査するディレクトリのデバイス
+    device: Option<u64>,
+}
+
+/// 走査中のディレクトリとその祖先 (シンボリックリンクをたどって来た経路を含む)
+struct Ancestor<'a> {
+    /// デバイスと inode 番号
+    id: Option<(u64, u64)>,
+    /// シンボリックリンクを解決した実際の場所
+    real: &'a Path,
+    parent: Option<&'a Ancestor<'a>>,
+}
+
+impl Ancestor<'_> {
+    /// `id` (`real`) のディレクトリにもう一度入ることになるか
+    fn is_loop(&self, id: Option<(u64, u64)>, real: &Path) -> bool {
+        let mut ancestor = Some(self);
+        while let Some(a) = ancestor {
+            if (id.is_some() && a.id == id) || a.real == real {
+                return true;
+            }
+            ancestor = a.parent;
+        }
+        false
+    }
+}
+
+impl Walker<'_> {
+    /// `dir` 以下を走査する (`here.real` はシンボリックリンクを解決した `dir` の実際の場所)
+    fn walk(&self, dir: &Path, here: &Ancestor<'_>, depth: usize) -> Vec<Found> {
+        let real = here.real;
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) => return vec![Found::Error(dir.to_path_buf(), e.to_string())],
+        };
+
+        let mut found = Vec::new();
+        let mut subdirs = Vec::new();
+        for entry in entries {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    found.push(Found::Error(dir.to_path_buf(), e.to_string()));
+                    continue;
+                }
+            };
+            let path = entry.path();
+            if entry.file_name() == ".git" {
+                // ワークツリーとサブモジュールの `.git` はディレクトリではなくファイル
+                if path.is_dir() {
+                    found.push(Found::Repo(real.to_path_buf()));
+                    continue;
+                }
+                match read_gitfile(&path) {
+                    GitFile::Worktree(main) => found.push(Found::Linked(dir.to_path_buf(), main)),
+                    // サブモジュールは親リポジトリと一緒に移動する
+                    GitFile::Submodule => {}
+                    GitFile::Other => found.push(Found::Repo(real.to_path_buf())),
+                }
+                continue;
+            }
+
+            let Some((id, sub_real)) = self.enter(&entry, here, depth + 1) else {
+                continue;
+            };
+            // ベアリポジトリは `.git` を持たないので中身で判定する
+            if looks_like_bare(&path) {
+                found.push(Found::Repo(sub_real));
+                continue;
+            }
+            subdirs.push((path, id, sub_real));
+        }
+
+        found.par_extend(
+            subdirs
+                .into_par_iter()
+                .flat_map_iter(|(path, id, sub_real)| {
+                    let sub = Ancestor {
+                        id,
+                        real: &sub_real,
+                        parent: Some(here),
+                    };
+                    self.walk(&path, &sub, depth + 1)
+                }),
+        );
+        found
+    }
+
+    /// `entry` を走査するなら、その ID と実際の場所を返す
+    fn enter(
+        &self,
+        entry: &fs::DirEntry,
+        here: &Ancestor<'_>,
+        depth: usize,
+    ) -> Option<(Option<(u64, u64)>, PathBuf)> {
+        if self.opts.max_depth.is_some_and(|max| depth > max) {
+            return None;
+        }
+        let path = entry.path();
+        let file_type = entry.file_type().ok()?;
+        let sub_real = if file_type.is_dir() {
+            here.real.join(entry.file_name())
+        } else if file_type.is_symlink() && self.opts.follow_links && path.is_dir() {
+            fs::canonicalize(&path).ok()?
+        } else {
+            return None;
+        };
+        let meta = fs::metadata(&path).ok()?;
+        let id = file_id(&meta);
+        // 兄弟同士を指し合うリンクもあるので、たどって来た経路全体と比べる
+        if here.is_loop(id, &sub_real) {
+            debug!("🔁 Not following {:?}: it loops back to an ancestor", path);
+            return None;
+        }
+
+        let relative = path.strip_prefix(self.base).unwrap_or(&path);
+        if self.opts.filter.is_excluded(relative) {
+            debug!("🙈 Excluded {:?}", path);
+            return None;
+        }
+        if let Some(base_device) = self.device
+            && device(&meta) != Some(base_device)
+        {
+            debug!("💽 Not crossing into another filesystem at {:?}", path);
+            return None;
+        }
+        Some((id, sub_real))
+    }
+}
+
+#[cfg(unix)]
+fn device(meta: &fs::Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+
+    Some(meta.dev())
+}
+
+#[cfg(not(unix))]
+fn device(_meta: &fs::Metadata) -> Option<u64> {
+    None
+}
+
+#[cfg(unix)]
+fn file_id(meta: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+
+    Some((meta.dev(), meta.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_id(_meta: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+fn looks_like_bare(dir: &Path) -> bool {
+    dir.join("HEAD").is_file() && dir.join("objects").is_dir() && dir.join("refs").is_dir()
+}
+
+enum GitFile {
+    /// リンクされたワークツリー (値はメインのリポジトリ)
+    Worktree(PathBuf),
+    Submodule,
+    /// `--separate-git-dir` など
+    Other,
+}
+
+/// `.git` ファイル (`gitdir: <path>`) の参照先から種類を判定する
+fn read_gitfile(path: &Path) -> GitFile {
+    let Ok(content) = fs::read_to_string(path) else {
+        return GitFile::Other;
+    };
+    let Some(target) = content.trim().strip_prefix("gitdir:") else {
+        return GitFile::Other;
+    };
+    let base = path.parent().unwrap_or(path);
+    let target = repair::clean(&base.join(target.trim()));
+
+    // <main>/.git/worktrees/<name> または <main>/.git/modules/<name>...
+    let mut ancestors = target.ancestors();
+    while let Some(dir) = ancestors.next() {
+        let kind = match dir.file_name().and_then(|n| n.to_str()) {
+            Some("worktrees") if target.parent() == Some(dir) => "worktrees",
+            Some("modules") => "modules",
+            _ => continue,
+        };
+        let Some(git_dir) = ancestors.next() else {
+            break;
+        };
+        return match kind {
+            "worktrees" if git_dir.file_name() == Some(".git".as_ref()) => {
+                GitFile::Worktree(git_dir.parent().unwrap_or(git_dir).to_path_buf())
+            }
+            "worktrees" => GitFile::Worktree(git_dir.to_path_buf()),
+            _ => GitFile::Submodule,
+        };
+    }
+    GitFile::Other
+}
+
+/// リポジトリを開き、リモート URL から移動先を決める
+pub fn inspect(repo_root: &Path, opts: &Options) -> Result<Repo, SkipReason> {
+    let mut repo =
+        Repository::open(repo_root).map_err(|e| SkipReason::OpenFailed(e.message().to_string()))?;
+    let remote_name = choose_remote(&repo, &opts.remotes)?;
+    let config = repo
+        .config()
+        .and_then(|mut c| c.snapshot())
+        .map_err(|e| SkipReason::OpenFailed(e.message().to_string()))?;
+    let raw_url = config
+        .get_string(&format!("remote.{}.url", remote_name))
+        .map_err(|_| SkipReason::NoUrl(remote_name.clone()))?;
+    let pushurl = config
+        .get_string(&format!("remote.{}.pushurl", remote_name))
+        .ok();
+
+    // insteadOf で書き換えた fetch 用の URL を優先し、使えなければ push 用の URL を試す
+    let rewrites = Rewrites::from_config(&config);
+    let mut candidates = vec![rewrites.fetch_url(&raw_url)];
+    let push_url = rewrites.push_url(&raw_url, pushurl.as_deref());
+    if !candidates.contains(&push_url) {
+        candidates.push(push_url);
+    }
+
+    let kind = if !repo.is_bare() {
+        RepoKind::Normal
+    } else if config
+        .get_bool(&format!("remote.{}.mirror", remote_name))
+        .unwrap_or(false)
+    {
+        RepoKind::Mirror
+    } else {
+        RepoKind::Bare
+    };
+
+    let mut first_err = None;
+    for url in candidates {
+        let (dest, relative) = match destination(&url, opts) {
+            Ok(found) => found,
+            Err(e) => {
+                first_err.get_or_insert(e);
+                continue;
+            }
+        };
+        return Ok(Repo {
+            src: repo_root.to_path_buf(),
+            // `ghq get --bare` と同じく `<repo>.git` に置く
+            dest: match kind {
+                RepoKind::Normal => dest,
+                RepoKind::Bare | RepoKind::Mirror => with_git_suffix(dest),
+            },
+            relative,
+            kind,
+            remote: remote_name,
+            url,
+            nested_in: None,
+            worktrees: list_worktrees(&repo),
+            safety: Safety::check(&mut repo),
+        });
+    }
+    Err(first_err.unwrap_or(SkipReason::NoUrl(remote_name)))
+}
+
+/// 移動先と、ghq ルートからの相対パス
+fn destination(url: &str, opts: &Options) -> Result<(PathBuf, PathBuf), SkipReason> {
+    let git_url =
+        git_url_parse::GitUrl::parse(url).map_err(|_| SkipReason::InvalidUrl(url.to_string()))?;
+    let (host, path) = layout::host_and_path(&git_url);
+    let resolved = match &opts.ssh_config {
+        Some(ssh_config) if git_url.scheme() == Some("ssh") => ssh_config.resolve(host),
+        _ => None,
+    };
+    let host = resolved.as_deref().unwrap_or(host);
+    let relative =
+        layout::relative_path(host, path).map_err(|e| SkipReason::Layout(url.to_string(), e))?;
+
+    Ok((opts.dests.root_for(host, path).join(&relative), relative))
+}
+
+fn with_git_suffix(path: PathBuf) -> PathBuf {
+    let mut path = path.into_os_string();
+    path.push(".git");
+    PathBuf::from(path)
+}
+
+fn list_worktrees(repo: &Repository) -> Vec<Worktree> {
+    let Ok(names) = repo.worktrees() else {
+        return Vec::new();
+    };
+    names
+        .iter()
+        .flatten()
+        .filter_map(|name| {
+            let worktree = repo.find_worktree(name).ok()?;
+            Some(Worktree {
+                name: name.to_string(),
+                path: worktree.path().to_path_buf(),
+            })
+        })
+        .collect()
+}
+
+/// 優先リストの順に探し、見つからなければ唯一のリモートを使う
+fn choose_remote(repo: &Repository, priority: &[String]) -> Result<String, SkipReason> {
+    let names: Vec<String> = repo
+        .remotes()
+        .map(|names| names.iter().flatten().map(str::to_string).collect())
+        .unwrap_or_default();
+
+    if let Some(name) = priority.iter().find(|p| names.contains(p)) {
+        return Ok(name.clone());
+    }
+    match names.as_slice() {
+        [] => Err(SkipReason::NoRemote),
+        [only] => Ok(only.clone()),
+        _ => Err(SkipReason::AmbiguousRemotes(names)),
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::{fs, os::unix::fs::symlink, path::PathBuf};
+
+    use super::{Options, find_git_repos};
+    use crate::{filter::Filter, ghq::Destinations};
+
+    fn options(follow_links: bool) -> Options {
+        Options {
+            dests: Destinations {
+                root: PathBuf::from("/nonexistent/ghq"),
+                url_roots: Vec::new(),
+            },
+            remotes: Vec::new(),
+            ssh_config: None,
+            filter: Filter::default(),
+            max_depth: None,
+            one_file_system: false,
+            follow_links,
+        }
+    }
+
+    #[test]
+    fn stops_at_symlink_loops_between_siblings() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().canonicalize().unwrap();
+        fs::create_dir_all(base.join("a/repo/.git")).unwrap();
+        fs::create_dir(base.join("b")).unwrap();
+        for (link, target) in [
+            ("a/l1", "../b"),
+            ("a/l2", "../b"),
+            ("b/l1", "../a"),
+            ("b/l2", "../a"),
+        ] {
+            symlink(target, base.join(link)).unwrap();
+        }
+
+        let scan = find_git_repos(&base, &options(true)).unwrap();
+        // `.git` の中身がないので開けないが、1 か所でだけ見つかればよい
+        let found: Vec<_> = scan.skipped.iter().map(|s| s.path.clone()).collect();
+        assert_eq!(found, [base.join("a/repo")]);
+    }
+
+    #[test]
+    fn tells_why_linked_worktrees_stay() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().canonicalize().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        let outside = outside.path().canonicalize().unwrap();
+        // リモートがないので移動しないリポジトリと、そのワークツリー
+        git2::Repository::init(base.join("m")).unwrap();
+        for (worktree, main) in [("wt", base.join("m")), ("far", outside.join("m"))] {
+            fs::create_dir(base.join(worktree)).unwrap();
+            fs::write(
+                base.join(worktree).join(".git"),
+                format!("gitdir: {}\n", main.join(".git/worktrees/wt").display()),
+            )
+            .unwrap();
+        }
+
+        let scan = find_git_repos(&base, &options(false)).unwrap();
+        let skipped: Vec<_> = scan
+            .skipped
+            .iter()
+            .map(|s| (s.path.clone(), s.reason.code()))
+            .collect();
+        assert_eq!(
+            skipped,
+            [
+                (base.join("far"), "linked_worktree"),
+                (base.join("m"), "no_remote"),
+                (base.join("wt"), "main_skipped"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,321 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+/// `~/.ssh/config` の `Host` / `HostName` だけを読んだもの
+#[derive(Debug, Clone, Default)]
+pub struct SshConfig {
+    blocks: Vec<HostBlock>,
+}
+
+#[derive(Debug, Clone)]
+struct HostBlock {
+    patterns: Vec<String>,
+    hostname: Option<String>,
+}
+
+impl SshConfig {
+    /// `~/.ssh/config` を読み込む (存在しなければ空)
+    pub fn load() -> Self {
+        let Some(home) = env::home_dir() else {
+            return Self::default();
+        };
+        let mut config = Self::default();
+        config.read_file(&home.join(".ssh").join("config"), &home.join(".ssh"), 0);
+        config
+    }
+
+    /// エイリアスを `HostName` で指定された実際のホスト名に解決する
+    ///
+    /// ssh と同様に、最初に一致した `HostName` が使われる。
+    pub fn resolve(&self, alias: &str) -> Option<String> {
+        self.blocks
+            .iter()
+            .filter(|b| b.matches(alias))
+            .find_map(|b| b.hostname.as_deref())
+            .map(|h| expand_tokens(h, alias))
+    }
+
+    fn read_file(&mut self, path: &Path, ssh_dir: &Path, depth: usize) {
+        // Include のループを防ぐ
+        if depth > 16 {
+            return;
+        }
+        let Ok(content) = fs::read_to_string(path) else {
+            return;
+        };
+        self.parse(&content, ssh_dir, depth);
+    }
+
+    fn parse(&mut self, content: &str, ssh_dir: &Path, depth: usize) {
+        if depth == 0 {
+            // 最初の Host 行より前の設定はすべてのホストに適用される
+            self.blocks.push(HostBlock {
+                patterns: vec!["*".to_string()],
+                hostname: None,
+            });
+        }
+
+        for line in content.lines() {
+            let Some((key, value)) = split_line(line) else {
+                continue;
+            };
+            match key.to_lowercase().as_str() {
+                "host" => self.blocks.push(HostBlock {
+                    patterns: value.split_whitespace().map(str::to_string).collect(),
+                    hostname: None,
+                }),
+                // Match の条件は評価しないので、続く設定はどのホストにも適用しない
+                "match" => self.blocks.push(HostBlock {
+                    patterns: Vec::new(),
+                    hostname: None,
+                }),
+                "hostname" => {
+                    if let Some(block) = self.blocks.last_mut()
+                        && block.hostname.is_none()
+                    {
+                        block.hostname = Some(value.to_string());
+                    }
+                }
+                "include" => {
+                    for pattern in value.split_whitespace() {
+                        for file in expand_include(pattern, ssh_dir) {
+                            self.read_file(&file, ssh_dir, depth + 1);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+impl HostBlock {
+    fn matches(&self, host: &str) -> bool {
+        let mut matched = false;
+        for pattern in &self.patterns {
+            if let Some(negated) = pattern.strip_prefix('!') {
+                if wildcard_match(negated, host) {
+                    return false;
+                }
+            } else if wildcard_match(pattern, host) {
+                matched = true;
+            }
+        }
+        matched
+    }
+}
+
+/// `Key value` / `Key=value` 形式の行を分割する
+fn split_line(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    let idx = line.find(|c: char| c.is_whitespace() || c == '=')?;
+    let (key, rest) = line.split_at(idx);
+    let value = rest
+        .trim_start_matches(|c: char| c.is_whitespace())
+        .trim_start_matches('=')
+        .trim()
+        .trim_matches('"');
+    Some((key, value))
+}
+
+/// `*` と `?` を使った ssh のパターンに一致するか (大文字小文字は区別しない)
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((bp, bt)) = backtrack {
+            p = bp + 1;
+            t = bt + 1;
+            backtrack = Some((bp, bt + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// `%h` (元のホスト名) と `%%` を展開する
+fn expand_tokens(hostname: &str, alias: &str) -> String {
+    hostname
+        .replace("%%", "\0")
+        .replace("%h", alias)
+        .replace('\0', "%")
+}
+
+/// Include のパスを解決する (相対パスは `~/.ssh` 基準、ファイル名の部分のワイルドカードに対応)
+fn expand_include(pattern: &str, ssh_dir: &Path) -> Vec<PathBuf> {
+    let path = match pattern.strip_prefix("~/") {
+        Some(rest) => env::home_dir().map_or_else(|| PathBuf::from(pattern), |h| h.join(rest)),
+        None => ssh_dir.join(pattern),
+    };
+
+    let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+        return Vec::new();
+    };
+    if !name.contains(['*', '?']) {
+        return vec![path];
+    }
+    let Some(dir) = path.parent() else {
+        return Vec::new();
+    };
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut files: Vec<PathBuf> = entries
+        .flatten()
+        .filter(|e| {
+            e.file_name()
+                .to_str()
+                .is_some_and(|n| wildcard_match(name, n))
+        })
+        .map(|e| e.path())
+        .collect();
+    files.sort();
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use super::SshConfig;
+
+    fn resolve(config: &str, ssh_dir: &Path, alias: &str) -> Option<String> {
+        let mut ssh_config = SshConfig::default();
+        ssh_config.parse(config, ssh_dir, 0);
+        ssh_config.resolve(alias)
+    }
+
+    #[test]
+    fn resolves_host_aliases() {
+        let cases = [
+            ("Host gh\n  HostName github.com", "gh", Some("github.com")),
+            ("Host gh\n  HostName github.com", "gl", None),
+            (
+                "Host gh work\n  HostName github.com",
+                "work",
+                Some("github.com"),
+            ),
+            ("HOST gh\n  hostname github.com", "GH", Some("github.com")),
+            // 最初に一致したものが使われる
+            (
+                "Host gh\n  HostName first.example.com\nHost *\n  HostName second.example.com",
+                "gh",
+                Some("first.example.com"),
+            ),
+            (
+                "Host gh\n  HostName first.example.com\nHost *\n  HostName second.example.com",
+                "other",
+                Some("second.example.com"),
+            ),
+            (
+                "Host gh\n  HostName a.example.com\n  HostName b.example.com",
+                "gh",
+                Some("a.example.com"),
+            ),
+            // 最初の Host 行より前の設定はすべてのホストに適用される
+            (
+                "HostName global.example.com\nHost gh\n  HostName github.com",
+                "gh",
+                Some("global.example.com"),
+            ),
+            ("Host * !work\n  HostName x.example.com", "work", None),
+            (
+                "Host * !work\n  HostName x.example.com",
+                "home",
+                Some("x.example.com"),
+            ),
+            (
+                "Host gh?\n  HostName x.example.com",
+                "gh1",
+                Some("x.example.com"),
+            ),
+            ("Host gh?\n  HostName x.example.com", "gh12", None),
+            (
+                "Host *.internal\n  HostName %h.example.com",
+                "db.internal",
+                Some("db.internal.example.com"),
+            ),
+            ("Host pct\n  HostName a%%h", "pct", Some("a%h")),
+            (
+                "Host=eq\nHostName=eq.example.com",
+                "eq",
+                Some("eq.example.com"),
+            ),
+            (
+                "Host eq\n  HostName = spaced.example.com",
+                "eq",
+                Some("spaced.example.com"),
+            ),
+            (
+                "Host q\n  HostName \"quoted.example.com\"",
+                "q",
+                Some("quoted.example.com"),
+            ),
+            (
+                "# Host gh\n#  HostName github.com\n\nHost gh\n  User git",
+                "gh",
+                None,
+            ),
+            // Match の条件は評価しない
+            ("Match host gh\n  HostName github.com", "gh", None),
+        ];
+
+        for (config, alias, expected) in cases {
+            assert_eq!(
+                resolve(config, Path::new("/nonexistent"), alias).as_deref(),
+                expected,
+                "{:?} with {:?}",
+                alias,
+                config
+            );
+        }
+    }
+
+    #[test]
+    fn reads_included_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("conf.d")).unwrap();
+        fs::write(
+            dir.path().join("conf.d/a.conf"),
+            "Host a\n  HostName a.example.com",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("conf.d/b.conf"),
+            "Host b\n  HostName b.example.com",
+        )
+        .unwrap();
+        fs::write(dir.path().join("other"), "Host c\n  HostName c.example.com").unwrap();
+
+        let config = "Include conf.d/*.conf other\nHost *\n  HostName fallback.example.com";
+        let cases = [
+            ("a", "a.example.com"),
+            ("b", "b.example.com"),
+            ("c", "c.example.com"),
+            ("d", "fallback.example.com"),
+        ];
+        for (alias, expected) in cases {
+            assert_eq!(
+                resolve(config, dir.path(), alias).as_deref(),
+                Some(expected),
+                "{}",
+                alias
+            );
+        }
+    }
+}